@@ -21,6 +21,7 @@ use grammers_tl_types as tl;
 
 use crate::types::{Member, PhoneCallDiscardReason};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize)]
 pub enum Action {
@@ -47,9 +48,119 @@ pub enum Action {
         invites: Vec<Member>,
     },
     HistoryClear {},
+    PinMessage {
+        message_id: Option<i32>,
+    },
+    ChatAddUser {
+        users: Vec<Member>,
+    },
+    ChatDeleteUser {
+        user: Member,
+    },
+    ChatJoinedByLink {
+        inviter: Member,
+    },
+    ChatJoinedByRequest {},
+    ChatMigrateTo {
+        channel_id: i64,
+    },
+    ChannelCreate {
+        title: String,
+    },
+    ChannelMigrateFrom {
+        title: String,
+        chat_id: i64,
+    },
+    ChatEditPhoto {
+        photo_id: Option<i64>,
+    },
+    ChatDeletePhoto {},
+    ScreenshotTaken {},
+    SetMessagesTtl {
+        period: i32,
+    },
+    PaymentSent {
+        currency: String,
+        total_amount: i64,
+        recurring_init: bool,
+        recurring_used: bool,
+    },
+    CustomAction {
+        message: String,
+    },
+    TopicCreate {
+        title: String,
+        icon_color: i32,
+        icon_emoji_id: Option<i64>,
+    },
+    TopicEdit {
+        title: Option<String>,
+        icon_emoji_id: Option<i64>,
+        closed: Option<bool>,
+        hidden: Option<bool>,
+    },
     UnsupportedByTgBackup(String),
 }
 
+impl Action {
+    /// Convert the action of a service message. `reply_to_message_id` is the pinned message
+    /// for the pin action.
+    pub fn from_tl(action: &tl::enums::MessageAction, reply_to_message_id: Option<i32>) -> Action {
+        match action {
+            tl::enums::MessageAction::PhoneCall(call) => call.into(),
+            tl::enums::MessageAction::ChatCreate(chat_create) => chat_create.into(),
+            tl::enums::MessageAction::ChatEditTitle(edit_title) => edit_title.into(),
+            tl::enums::MessageAction::GroupCall(group_call) => group_call.into(),
+            tl::enums::MessageAction::InviteToGroupCall(invite_to_group_call) => {
+                invite_to_group_call.into()
+            }
+            tl::enums::MessageAction::HistoryClear => Action::HistoryClear {},
+            tl::enums::MessageAction::PinMessage => Action::PinMessage {
+                message_id: reply_to_message_id,
+            },
+            tl::enums::MessageAction::ChatAddUser(add_user) => add_user.into(),
+            tl::enums::MessageAction::ChatDeleteUser(delete_user) => delete_user.into(),
+            tl::enums::MessageAction::ChatJoinedByLink(joined) => joined.into(),
+            tl::enums::MessageAction::ChatJoinedByRequest => Action::ChatJoinedByRequest {},
+            tl::enums::MessageAction::ChatMigrateTo(migrate_to) => migrate_to.into(),
+            tl::enums::MessageAction::ChannelCreate(channel_create) => channel_create.into(),
+            tl::enums::MessageAction::ChannelMigrateFrom(migrate_from) => migrate_from.into(),
+            tl::enums::MessageAction::ChatEditPhoto(edit_photo) => edit_photo.into(),
+            tl::enums::MessageAction::ChatDeletePhoto => Action::ChatDeletePhoto {},
+            tl::enums::MessageAction::ScreenshotTaken => Action::ScreenshotTaken {},
+            tl::enums::MessageAction::SetMessagesTtl(ttl) => ttl.into(),
+            tl::enums::MessageAction::PaymentSent(payment) => payment.into(),
+            tl::enums::MessageAction::CustomAction(custom) => custom.into(),
+            tl::enums::MessageAction::TopicCreate(topic_create) => topic_create.into(),
+            tl::enums::MessageAction::TopicEdit(topic_edit) => topic_edit.into(),
+            _ => Action::UnsupportedByTgBackup(format!("{:?}", action)),
+        }
+    }
+
+    /// Replace members that are known by id only with the full information from `users`
+    pub fn resolve_members(&mut self, users: &HashMap<i64, Member>) {
+        match self {
+            Action::InviteToGroupCall { invites, .. } => invites
+                .iter_mut()
+                .for_each(|member| resolve_member(member, users)),
+            Action::ChatAddUser { users: added } => added
+                .iter_mut()
+                .for_each(|member| resolve_member(member, users)),
+            Action::ChatDeleteUser { user } => resolve_member(user, users),
+            Action::ChatJoinedByLink { inviter } => resolve_member(inviter, users),
+            _ => {}
+        }
+    }
+}
+
+fn resolve_member(member: &mut Member, users: &HashMap<i64, Member>) {
+    if let Member::IdOnly { id } = member {
+        if let Some(user) = users.get(id) {
+            *member = user.clone();
+        }
+    }
+}
+
 impl From<&tl::types::MessageActionPhoneCall> for Action {
     fn from(data: &tl::types::MessageActionPhoneCall) -> Self {
         let reason = data.reason.as_ref().map(|it| match it {
@@ -107,7 +218,6 @@ impl From<&tl::types::MessageActionInviteToGroupCall> for Action {
         let tl::enums::InputGroupCall::Call(group_call) = &data.call;
         let mut invites = vec![];
         for user in &data.users {
-            // TODO convert to member
             invites.push(Member::IdOnly { id: *user })
         }
         Action::InviteToGroupCall {
@@ -118,6 +228,119 @@ impl From<&tl::types::MessageActionInviteToGroupCall> for Action {
     }
 }
 
+impl From<&tl::types::MessageActionChatAddUser> for Action {
+    fn from(data: &tl::types::MessageActionChatAddUser) -> Self {
+        Action::ChatAddUser {
+            users: data
+                .users
+                .iter()
+                .map(|id| Member::IdOnly { id: *id })
+                .collect(),
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionChatDeleteUser> for Action {
+    fn from(data: &tl::types::MessageActionChatDeleteUser) -> Self {
+        Action::ChatDeleteUser {
+            user: Member::IdOnly { id: data.user_id },
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionChatJoinedByLink> for Action {
+    fn from(data: &tl::types::MessageActionChatJoinedByLink) -> Self {
+        Action::ChatJoinedByLink {
+            inviter: Member::IdOnly {
+                id: data.inviter_id,
+            },
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionChatMigrateTo> for Action {
+    fn from(data: &tl::types::MessageActionChatMigrateTo) -> Self {
+        Action::ChatMigrateTo {
+            channel_id: data.channel_id,
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionChannelCreate> for Action {
+    fn from(data: &tl::types::MessageActionChannelCreate) -> Self {
+        Action::ChannelCreate {
+            title: data.title.to_string(),
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionChannelMigrateFrom> for Action {
+    fn from(data: &tl::types::MessageActionChannelMigrateFrom) -> Self {
+        Action::ChannelMigrateFrom {
+            title: data.title.to_string(),
+            chat_id: data.chat_id,
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionChatEditPhoto> for Action {
+    fn from(data: &tl::types::MessageActionChatEditPhoto) -> Self {
+        let photo_id = match &data.photo {
+            tl::enums::Photo::Photo(photo) => Some(photo.id),
+            tl::enums::Photo::Empty(_) => None,
+        };
+        Action::ChatEditPhoto { photo_id }
+    }
+}
+
+impl From<&tl::types::MessageActionSetMessagesTtl> for Action {
+    fn from(data: &tl::types::MessageActionSetMessagesTtl) -> Self {
+        Action::SetMessagesTtl {
+            period: data.period,
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionPaymentSent> for Action {
+    fn from(data: &tl::types::MessageActionPaymentSent) -> Self {
+        Action::PaymentSent {
+            currency: data.currency.to_string(),
+            total_amount: data.total_amount,
+            recurring_init: data.recurring_init,
+            recurring_used: data.recurring_used,
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionCustomAction> for Action {
+    fn from(data: &tl::types::MessageActionCustomAction) -> Self {
+        Action::CustomAction {
+            message: data.message.to_string(),
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionTopicCreate> for Action {
+    fn from(data: &tl::types::MessageActionTopicCreate) -> Self {
+        Action::TopicCreate {
+            title: data.title.to_string(),
+            icon_color: data.icon_color,
+            icon_emoji_id: data.icon_emoji_id,
+        }
+    }
+}
+
+impl From<&tl::types::MessageActionTopicEdit> for Action {
+    fn from(data: &tl::types::MessageActionTopicEdit) -> Self {
+        Action::TopicEdit {
+            title: data.title.clone(),
+            icon_emoji_id: data.icon_emoji_id,
+            closed: data.closed,
+            hidden: data.hidden,
+        }
+    }
+}
+
 /*
 // I don't know how to call it
 impl From<&tl::types::MessageActionHistoryClear> for Action {
@@ -126,3 +349,393 @@ impl From<&tl::types::MessageActionHistoryClear> for Action {
     }
 }
 */
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn check(action: Action, expected: Value) {
+        let serialized = serde_json::to_value(&action).unwrap();
+        assert_eq!(expected, serialized);
+        let deserialized: Action = serde_json::from_value(serialized.clone()).unwrap();
+        assert_eq!(serialized, serde_json::to_value(&deserialized).unwrap());
+    }
+
+    fn user(id: i64) -> Member {
+        Member::User {
+            id,
+            username: None,
+            first_name: String::from("Alex"),
+            last_name: None,
+            verified: false,
+            contact: true,
+            mutual_contact: true,
+            deleted: false,
+            avatars: vec![],
+            details: None,
+        }
+    }
+
+    #[test]
+    fn resolve_invites() {
+        let mut users = HashMap::new();
+        users.insert(1, user(1));
+        let mut action = Action::InviteToGroupCall {
+            id: 0,
+            access_hash: 0,
+            invites: vec![Member::IdOnly { id: 1 }, Member::IdOnly { id: 2 }],
+        };
+        action.resolve_members(&users);
+        if let Action::InviteToGroupCall { invites, .. } = action {
+            assert!(user(1) == invites[0]);
+            assert!(Member::IdOnly { id: 2 } == invites[1]);
+        } else {
+            panic!("Unexpected action")
+        }
+    }
+
+    #[test]
+    fn resolve_deleted_user() {
+        let mut users = HashMap::new();
+        users.insert(1, user(1));
+        let mut action = Action::ChatDeleteUser {
+            user: Member::IdOnly { id: 1 },
+        };
+        action.resolve_members(&users);
+        check(
+            action,
+            json!({"ChatDeleteUser": {"user": {
+                "type": "User",
+                "id": 1,
+                "username": null,
+                "first_name": "Alex",
+                "last_name": null,
+                "verified": false,
+                "contact": true,
+                "mutual_contact": true,
+                "deleted": false,
+                "avatars": [],
+                "details": null
+            }}}),
+        );
+    }
+
+    #[test]
+    fn pin_message() {
+        check(
+            Action::PinMessage {
+                message_id: Some(10),
+            },
+            json!({"PinMessage": {"message_id": 10}}),
+        );
+    }
+
+    #[test]
+    fn chat_add_user() {
+        check(
+            Action::ChatAddUser {
+                users: vec![Member::IdOnly { id: 1 }],
+            },
+            json!({"ChatAddUser": {"users": [{"type": "IdOnly", "id": 1}]}}),
+        );
+    }
+
+    #[test]
+    fn chat_delete_user() {
+        check(
+            Action::ChatDeleteUser {
+                user: Member::IdOnly { id: 1 },
+            },
+            json!({"ChatDeleteUser": {"user": {"type": "IdOnly", "id": 1}}}),
+        );
+    }
+
+    #[test]
+    fn chat_joined_by_link() {
+        check(
+            Action::ChatJoinedByLink {
+                inviter: Member::IdOnly { id: 1 },
+            },
+            json!({"ChatJoinedByLink": {"inviter": {"type": "IdOnly", "id": 1}}}),
+        );
+    }
+
+    #[test]
+    fn chat_joined_by_request() {
+        check(
+            Action::ChatJoinedByRequest {},
+            json!({"ChatJoinedByRequest": {}}),
+        );
+    }
+
+    #[test]
+    fn chat_migrate_to() {
+        check(
+            Action::ChatMigrateTo { channel_id: 42 },
+            json!({"ChatMigrateTo": {"channel_id": 42}}),
+        );
+    }
+
+    #[test]
+    fn channel_create() {
+        check(
+            Action::ChannelCreate {
+                title: String::from("News"),
+            },
+            json!({"ChannelCreate": {"title": "News"}}),
+        );
+    }
+
+    #[test]
+    fn channel_migrate_from() {
+        check(
+            Action::ChannelMigrateFrom {
+                title: String::from("Team"),
+                chat_id: 42,
+            },
+            json!({"ChannelMigrateFrom": {"title": "Team", "chat_id": 42}}),
+        );
+    }
+
+    #[test]
+    fn chat_edit_photo() {
+        check(
+            Action::ChatEditPhoto { photo_id: Some(7) },
+            json!({"ChatEditPhoto": {"photo_id": 7}}),
+        );
+    }
+
+    #[test]
+    fn chat_delete_photo() {
+        check(Action::ChatDeletePhoto {}, json!({"ChatDeletePhoto": {}}));
+    }
+
+    #[test]
+    fn screenshot_taken() {
+        check(Action::ScreenshotTaken {}, json!({"ScreenshotTaken": {}}));
+    }
+
+    #[test]
+    fn set_messages_ttl() {
+        check(
+            Action::SetMessagesTtl { period: 86400 },
+            json!({"SetMessagesTtl": {"period": 86400}}),
+        );
+    }
+
+    #[test]
+    fn payment_sent() {
+        check(
+            Action::PaymentSent {
+                currency: String::from("EUR"),
+                total_amount: 1000,
+                recurring_init: false,
+                recurring_used: false,
+            },
+            json!({"PaymentSent": {
+                "currency": "EUR",
+                "total_amount": 1000,
+                "recurring_init": false,
+                "recurring_used": false
+            }}),
+        );
+    }
+
+    #[test]
+    fn custom_action() {
+        check(
+            Action::CustomAction {
+                message: String::from("Hi"),
+            },
+            json!({"CustomAction": {"message": "Hi"}}),
+        );
+    }
+
+    #[test]
+    fn topic_create() {
+        check(
+            Action::TopicCreate {
+                title: String::from("Topic"),
+                icon_color: 1,
+                icon_emoji_id: None,
+            },
+            json!({"TopicCreate": {"title": "Topic", "icon_color": 1, "icon_emoji_id": null}}),
+        );
+    }
+
+    #[test]
+    fn topic_edit() {
+        check(
+            Action::TopicEdit {
+                title: None,
+                icon_emoji_id: None,
+                closed: Some(true),
+                hidden: None,
+            },
+            json!({"TopicEdit": {
+                "title": null,
+                "icon_emoji_id": null,
+                "closed": true,
+                "hidden": null
+            }}),
+        );
+    }
+
+    fn convert(action: tl::enums::MessageAction) -> Value {
+        serde_json::to_value(Action::from_tl(&action, Some(10))).unwrap()
+    }
+
+    #[test]
+    fn convert_pin_message() {
+        assert_eq!(
+            json!({"PinMessage": {"message_id": 10}}),
+            convert(tl::enums::MessageAction::PinMessage)
+        );
+    }
+
+    #[test]
+    fn convert_chat_users() {
+        assert_eq!(
+            json!({"ChatAddUser": {"users": [{"type": "IdOnly", "id": 1}]}}),
+            convert(tl::enums::MessageAction::ChatAddUser(
+                tl::types::MessageActionChatAddUser { users: vec![1] }
+            ))
+        );
+        assert_eq!(
+            json!({"ChatDeleteUser": {"user": {"type": "IdOnly", "id": 1}}}),
+            convert(tl::enums::MessageAction::ChatDeleteUser(
+                tl::types::MessageActionChatDeleteUser { user_id: 1 }
+            ))
+        );
+        assert_eq!(
+            json!({"ChatJoinedByLink": {"inviter": {"type": "IdOnly", "id": 1}}}),
+            convert(tl::enums::MessageAction::ChatJoinedByLink(
+                tl::types::MessageActionChatJoinedByLink { inviter_id: 1 }
+            ))
+        );
+        assert_eq!(
+            json!({"ChatJoinedByRequest": {}}),
+            convert(tl::enums::MessageAction::ChatJoinedByRequest)
+        );
+    }
+
+    #[test]
+    fn convert_migrate() {
+        assert_eq!(
+            json!({"ChatMigrateTo": {"channel_id": 42}}),
+            convert(tl::enums::MessageAction::ChatMigrateTo(
+                tl::types::MessageActionChatMigrateTo { channel_id: 42 }
+            ))
+        );
+        assert_eq!(
+            json!({"ChannelMigrateFrom": {"title": "Team", "chat_id": 42}}),
+            convert(tl::enums::MessageAction::ChannelMigrateFrom(
+                tl::types::MessageActionChannelMigrateFrom {
+                    title: String::from("Team"),
+                    chat_id: 42,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn convert_chat_photo() {
+        assert_eq!(
+            json!({"ChatEditPhoto": {"photo_id": null}}),
+            convert(tl::enums::MessageAction::ChatEditPhoto(
+                tl::types::MessageActionChatEditPhoto {
+                    photo: tl::enums::Photo::Empty(tl::types::PhotoEmpty { id: 7 }),
+                }
+            ))
+        );
+        assert_eq!(
+            json!({"ChatDeletePhoto": {}}),
+            convert(tl::enums::MessageAction::ChatDeletePhoto)
+        );
+    }
+
+    #[test]
+    fn convert_screenshot_taken() {
+        assert_eq!(
+            json!({"ScreenshotTaken": {}}),
+            convert(tl::enums::MessageAction::ScreenshotTaken)
+        );
+    }
+
+    #[test]
+    fn convert_set_messages_ttl() {
+        assert_eq!(
+            json!({"SetMessagesTtl": {"period": 86400}}),
+            convert(tl::enums::MessageAction::SetMessagesTtl(
+                tl::types::MessageActionSetMessagesTtl {
+                    period: 86400,
+                    auto_setting_from: None,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn convert_payment_sent() {
+        assert_eq!(
+            json!({"PaymentSent": {
+                "currency": "EUR",
+                "total_amount": 1000,
+                "recurring_init": true,
+                "recurring_used": false
+            }}),
+            convert(tl::enums::MessageAction::PaymentSent(
+                tl::types::MessageActionPaymentSent {
+                    recurring_init: true,
+                    recurring_used: false,
+                    currency: String::from("EUR"),
+                    total_amount: 1000,
+                    invoice_slug: None,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn convert_custom_action() {
+        assert_eq!(
+            json!({"CustomAction": {"message": "Hi"}}),
+            convert(tl::enums::MessageAction::CustomAction(
+                tl::types::MessageActionCustomAction {
+                    message: String::from("Hi"),
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn convert_topics() {
+        assert_eq!(
+            json!({"TopicCreate": {"title": "Topic", "icon_color": 1, "icon_emoji_id": 5}}),
+            convert(tl::enums::MessageAction::TopicCreate(
+                tl::types::MessageActionTopicCreate {
+                    title: String::from("Topic"),
+                    icon_color: 1,
+                    icon_emoji_id: Some(5),
+                }
+            ))
+        );
+        assert_eq!(
+            json!({"TopicEdit": {
+                "title": "New",
+                "icon_emoji_id": null,
+                "closed": true,
+                "hidden": null
+            }}),
+            convert(tl::enums::MessageAction::TopicEdit(
+                tl::types::MessageActionTopicEdit {
+                    title: Some(String::from("New")),
+                    icon_emoji_id: None,
+                    closed: Some(true),
+                    hidden: None,
+                }
+            ))
+        );
+    }
+}
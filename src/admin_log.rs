@@ -0,0 +1,361 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::types::{peer_names, to_date, PeerInfo};
+use chrono::{DateTime, Utc};
+use grammers_client::types::Chat;
+use grammers_client::Client;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+const FOLDER: &'static str = "admin_log";
+const STATE_FILE_NAME: &'static str = "admin_log.json";
+const PAGE_SIZE: i32 = 100;
+
+/// State of the admin log loading. Only events after the last saved one are loaded on the next run.
+#[derive(Serialize, Deserialize)]
+struct AdminLogState {
+    last_event_id: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdminLogEvent {
+    pub id: i64,
+    pub date: DateTime<Utc>,
+    pub user_id: i64,
+    pub user_name: Option<String>,
+    pub action: AdminLogAction,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AdminLogAction {
+    ChangeTitle {
+        prev_value: String,
+        new_value: String,
+    },
+    ChangeAbout {
+        prev_value: String,
+        new_value: String,
+    },
+    ChangeUsername {
+        prev_value: String,
+        new_value: String,
+    },
+    ChangePhoto {},
+    ToggleInvites {
+        new_value: bool,
+    },
+    ToggleSignatures {
+        new_value: bool,
+    },
+    TogglePreHistoryHidden {
+        new_value: bool,
+    },
+    ToggleNoForwards {
+        new_value: bool,
+    },
+    ToggleSlowMode {
+        prev_value: i32,
+        new_value: i32,
+    },
+    UpdatePinned {
+        message: AdminLogMessage,
+    },
+    EditMessage {
+        prev_message: AdminLogMessage,
+        new_message: AdminLogMessage,
+    },
+    DeleteMessage {
+        message: AdminLogMessage,
+    },
+    StopPoll {
+        message: AdminLogMessage,
+    },
+    ParticipantJoin {},
+    ParticipantLeave {},
+    ParticipantInvite {
+        user_id: Option<i64>,
+    },
+    ParticipantToggleBan {
+        user_id: Option<i64>,
+        banned: bool,
+    },
+    ParticipantToggleAdmin {
+        user_id: Option<i64>,
+        admin: bool,
+    },
+    DefaultBannedRights {},
+    ChangeLinkedChat {
+        prev_value: i64,
+        new_value: i64,
+    },
+    ChangeHistoryTtl {
+        prev_value: i32,
+        new_value: i32,
+    },
+    /// Events that are not supported yet. The type name is a stable snake case name of the event
+    Other {
+        type_name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdminLogMessage {
+    pub id: i32,
+    pub text: Option<String>,
+}
+
+/// Check if we can read the admin log of this chat
+pub fn is_admin(chat: &Chat) -> bool {
+    match chat {
+        Chat::Group(group) => match &group.raw {
+            tl::enums::Chat::Channel(channel) => channel.creator || channel.admin_rights.is_some(),
+            _ => false,
+        },
+        Chat::Channel(channel) => channel.raw.creator || channel.raw.admin_rights.is_some(),
+        Chat::User(_) => false,
+    }
+}
+
+/// Load new events of the admin log and save them into the `admin_log` folder of the chat.
+///
+/// Telegram keeps the events for the last 48 hours only, so the log should be saved regularly.
+pub async fn save_admin_log(client: &Client, chat: &Chat, chat_path: &Path, date: DateTime<Utc>) {
+    let channel = match chat.pack().try_to_input_channel() {
+        Some(channel) => channel,
+        None => return,
+    };
+    let folder = chat_path.join(FOLDER);
+    let _ = fs::create_dir_all(&folder);
+    let state_path = folder.join(STATE_FILE_NAME);
+    let last_event_id = File::open(&state_path)
+        .ok()
+        .and_then(|file| serde_json::from_reader::<_, AdminLogState>(BufReader::new(file)).ok())
+        .map_or(0, |state| state.last_event_id);
+
+    let mut events = vec![];
+    let mut max_id = 0;
+    let mut complete = true;
+    loop {
+        let request = tl::functions::channels::GetAdminLog {
+            channel: channel.clone(),
+            q: String::new(),
+            events_filter: None,
+            admins: None,
+            max_id,
+            min_id: last_event_id,
+            limit: PAGE_SIZE,
+        };
+        let tl::enums::channels::AdminLogResults::Results(results) =
+            match client.invoke(&request).await {
+                Ok(results) => results,
+                Err(e) => {
+                    log::error!("Cannot load admin log of {}: {}", chat.id(), e);
+                    complete = false;
+                    break;
+                }
+            };
+        let names = peer_names(&results.users, &[]);
+        let page_size = results.events.len();
+        for tl::enums::ChannelAdminLogEvent::Event(event) in results.events {
+            max_id = event.id;
+            events.push(AdminLogEvent {
+                id: event.id,
+                date: to_date(event.date),
+                user_id: event.user_id,
+                user_name: names.get(&PeerInfo::User { id: event.user_id }).cloned(),
+                action: event.action.into(),
+            });
+        }
+        if page_size < PAGE_SIZE as usize {
+            break;
+        }
+    }
+    if events.is_empty() {
+        return;
+    }
+    events.sort_by_key(|event| event.id);
+
+    let file_name = format!("events-{}.json", date.format("%Y%m%d%H%M%S"));
+    let file = File::create(folder.join(file_name)).unwrap();
+    serde_json::to_writer_pretty(&file, &events).unwrap();
+
+    // Pages are loaded from the newest events, so the older events are not loaded yet.
+    // Keep the previous state to load them on the next run.
+    if !complete {
+        return;
+    }
+    let state = AdminLogState {
+        last_event_id: events.last().unwrap().id,
+    };
+    let file = File::create(state_path).unwrap();
+    serde_json::to_writer_pretty(&file, &state).unwrap();
+}
+
+impl From<tl::enums::ChannelAdminLogEventAction> for AdminLogAction {
+    fn from(action: tl::enums::ChannelAdminLogEventAction) -> Self {
+        use tl::enums::ChannelAdminLogEventAction as Action;
+        match action {
+            Action::ChangeTitle(it) => AdminLogAction::ChangeTitle {
+                prev_value: it.prev_value,
+                new_value: it.new_value,
+            },
+            Action::ChangeAbout(it) => AdminLogAction::ChangeAbout {
+                prev_value: it.prev_value,
+                new_value: it.new_value,
+            },
+            Action::ChangeUsername(it) => AdminLogAction::ChangeUsername {
+                prev_value: it.prev_value,
+                new_value: it.new_value,
+            },
+            Action::ChangePhoto(_) => AdminLogAction::ChangePhoto {},
+            Action::ToggleInvites(it) => AdminLogAction::ToggleInvites {
+                new_value: it.new_value,
+            },
+            Action::ToggleSignatures(it) => AdminLogAction::ToggleSignatures {
+                new_value: it.new_value,
+            },
+            Action::TogglePreHistoryHidden(it) => AdminLogAction::TogglePreHistoryHidden {
+                new_value: it.new_value,
+            },
+            Action::ToggleNoForwards(it) => AdminLogAction::ToggleNoForwards {
+                new_value: it.new_value,
+            },
+            Action::ToggleSlowMode(it) => AdminLogAction::ToggleSlowMode {
+                prev_value: it.prev_value,
+                new_value: it.new_value,
+            },
+            Action::UpdatePinned(it) => AdminLogAction::UpdatePinned {
+                message: it.message.into(),
+            },
+            Action::EditMessage(it) => AdminLogAction::EditMessage {
+                prev_message: it.prev_message.into(),
+                new_message: it.new_message.into(),
+            },
+            Action::DeleteMessage(it) => AdminLogAction::DeleteMessage {
+                message: it.message.into(),
+            },
+            Action::StopPoll(it) => AdminLogAction::StopPoll {
+                message: it.message.into(),
+            },
+            Action::ParticipantJoin => AdminLogAction::ParticipantJoin {},
+            Action::ParticipantLeave => AdminLogAction::ParticipantLeave {},
+            Action::ParticipantInvite(it) => AdminLogAction::ParticipantInvite {
+                user_id: participant_id(&it.participant),
+            },
+            Action::ParticipantToggleBan(it) => AdminLogAction::ParticipantToggleBan {
+                user_id: participant_id(&it.new_participant),
+                banned: matches!(it.new_participant, tl::enums::ChannelParticipant::Banned(_)),
+            },
+            Action::ParticipantToggleAdmin(it) => AdminLogAction::ParticipantToggleAdmin {
+                user_id: participant_id(&it.new_participant),
+                admin: matches!(
+                    it.new_participant,
+                    tl::enums::ChannelParticipant::Admin(_)
+                        | tl::enums::ChannelParticipant::Creator(_)
+                ),
+            },
+            Action::DefaultBannedRights(_) => AdminLogAction::DefaultBannedRights {},
+            Action::ChangeLinkedChat(it) => AdminLogAction::ChangeLinkedChat {
+                prev_value: it.prev_value,
+                new_value: it.new_value,
+            },
+            Action::ChangeHistoryTtl(it) => AdminLogAction::ChangeHistoryTtl {
+                prev_value: it.prev_value,
+                new_value: it.new_value,
+            },
+            other => AdminLogAction::Other {
+                type_name: other_action_name(&other).to_string(),
+            },
+        }
+    }
+}
+
+/// Stable name of the action that has no own variant in `AdminLogAction`
+fn other_action_name(action: &tl::enums::ChannelAdminLogEventAction) -> &'static str {
+    use tl::enums::ChannelAdminLogEventAction as Action;
+    match action {
+        Action::ChangeStickerSet(_) => "change_sticker_set",
+        Action::ChangeLocation(_) => "change_location",
+        Action::ParticipantJoinByInvite(_) => "participant_join_by_invite",
+        Action::ParticipantJoinByRequest(_) => "participant_join_by_request",
+        Action::ExportedInviteDelete(_) => "exported_invite_delete",
+        Action::ExportedInviteRevoke(_) => "exported_invite_revoke",
+        Action::ExportedInviteEdit(_) => "exported_invite_edit",
+        Action::StartGroupCall(_) => "start_group_call",
+        Action::DiscardGroupCall(_) => "discard_group_call",
+        Action::ParticipantMute(_) => "participant_mute",
+        Action::ParticipantUnmute(_) => "participant_unmute",
+        Action::ParticipantVolume(_) => "participant_volume",
+        Action::ToggleGroupCallSetting(_) => "toggle_group_call_setting",
+        Action::SendMessage(_) => "send_message",
+        Action::ChangeAvailableReactions(_) => "change_available_reactions",
+        Action::ChangeUsernames(_) => "change_usernames",
+        Action::ToggleForum(_) => "toggle_forum",
+        Action::CreateTopic(_) => "create_topic",
+        Action::EditTopic(_) => "edit_topic",
+        Action::DeleteTopic(_) => "delete_topic",
+        Action::PinTopic(_) => "pin_topic",
+        Action::ToggleAntiSpam(_) => "toggle_anti_spam",
+        // Events with their own variant and events that are added in newer layers
+        _ => "unknown",
+    }
+}
+
+impl From<tl::enums::Message> for AdminLogMessage {
+    fn from(message: tl::enums::Message) -> Self {
+        match message {
+            tl::enums::Message::Message(message) => AdminLogMessage {
+                id: message.id,
+                text: Some(message.message),
+            },
+            tl::enums::Message::Service(message) => AdminLogMessage {
+                id: message.id,
+                text: None,
+            },
+            tl::enums::Message::Empty(message) => AdminLogMessage {
+                id: message.id,
+                text: None,
+            },
+        }
+    }
+}
+
+fn participant_id(participant: &tl::enums::ChannelParticipant) -> Option<i64> {
+    match participant {
+        tl::enums::ChannelParticipant::Participant(it) => Some(it.user_id),
+        tl::enums::ChannelParticipant::ParticipantSelf(it) => Some(it.user_id),
+        tl::enums::ChannelParticipant::Creator(it) => Some(it.user_id),
+        tl::enums::ChannelParticipant::Admin(it) => Some(it.user_id),
+        tl::enums::ChannelParticipant::Banned(it) => match &it.peer {
+            tl::enums::Peer::User(user) => Some(user.user_id),
+            _ => None,
+        },
+        tl::enums::ChannelParticipant::Left(it) => match &it.peer {
+            tl::enums::Peer::User(user) => Some(user.user_id),
+            _ => None,
+        },
+    }
+}
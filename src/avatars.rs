@@ -0,0 +1,83 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use grammers_client::types::photo_sizes::VecExt;
+use grammers_client::types::{Chat, Photo};
+use grammers_client::Client;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const AVATARS: &'static str = "avatars";
+
+/// Profile photos of users and chats. They are saved under `avatars/{id}` in the backup directory.
+pub struct Avatars {
+    path: PathBuf,
+    history: bool,
+}
+
+impl Avatars {
+    pub fn create(output_dir: &Path, history: bool) -> Avatars {
+        let path = output_dir.join(AVATARS);
+        let _ = fs::create_dir_all(&path);
+        Avatars { path, history }
+    }
+
+    /// Save the current profile photo of the chat or all of them if the history is enabled.
+    /// Returns paths of the saved photos relative to the backup directory.
+    pub async fn save(&self, client: &Client, chat: &Chat) -> Vec<String> {
+        let mut res = vec![];
+        let mut photos = client.iter_profile_photos(chat);
+        loop {
+            match photos.next().await {
+                Ok(Some(photo)) => {
+                    if let Some(path) = self.save_photo(chat.id(), &photo).await {
+                        res.push(path);
+                    }
+                    if !self.history {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("Cannot load avatars of {}: {}", chat.id(), e);
+                    break;
+                }
+            }
+        }
+        res
+    }
+
+    async fn save_photo(&self, owner_id: i64, photo: &Photo) -> Option<String> {
+        let id = photo.id()?;
+        let folder = self.path.join(owner_id.to_string());
+        let _ = fs::create_dir_all(&folder);
+        let file_name = format!("{}@photo.jpg", id);
+        let file_path = folder.join(file_name.as_str());
+        // Profile photos never change, so there is no need to download the same photo again
+        if !file_path.exists() {
+            let downloaded = photo.thumbs().largest()?.download(&file_path).await;
+            if let Err(e) = downloaded {
+                log::warn!("Cannot download avatar {} of {}: {}", id, owner_id, e);
+                return None;
+            }
+        }
+        Some(format!("{}/{}/{}", AVATARS, owner_id, file_name))
+    }
+}
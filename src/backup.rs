@@ -33,19 +33,33 @@ use tokio::task;
 use tokio::time::Duration;
 
 use crate::actions::Action;
+use crate::admin_log::{is_admin, save_admin_log};
+use crate::comments::save_comments;
 use crate::companion::ChannelState::{ASK, BACKUP};
 use crate::companion::ChannelsStateInfo;
-use crate::context::{ChatContext, MainContext, MainMutContext, FILE, PHOTO, ROUND, VOICE};
-use crate::ext::{ChatExt, MessageExt};
+use crate::contacts::save_contacts;
+use crate::context::{
+    ChatContext, MainContext, MainMutContext, COMMENTS, FILE, PHOTO, ROUND, THUMBNAIL, VOICE,
+};
+use crate::dialogs::save_dialogs;
+use crate::ext::{ChatExt, MessageExt, PhotoExt};
 use crate::in_progress::{InProgress, InProgressInfo};
+use crate::list_chats::list_chats;
 use crate::logs::init_logs;
-use crate::opts::{Opts, SubCommand};
-use crate::types::Attachment::{PhotoExpired, TooLarge};
+use crate::media_policy::{MediaPolicy, MediaType, MediaTypeRule, SkipReason};
+use crate::opts::{check_date_range, Opts, PhotoSizePolicy, SubCommand};
+use crate::polls::OpenPolls;
+use crate::rules::{load_rules, ChatRule, ChatSettings};
+use crate::scheduled::save_scheduled;
+use crate::selectors::{is_selected, ChatSelector};
+use crate::types::Attachment::PhotoExpired;
 use crate::types::{
     chat_to_info, msg_to_info, Attachment, BackUpInfo, ChatInfo, FileInfo, Member, MessageInfo,
+    PeerInfo,
 };
+use crate::users::statuses;
 use grammers_client::types::photo_sizes::VecExt;
-use grammers_client::types::{Dialog, Message};
+use grammers_client::types::{Chat, Dialog, Document, Message, Photo, User};
 use grammers_client::{Client, Config, SignInError};
 use grammers_mtproto::mtp::RpcError;
 use grammers_mtsender::{AuthorizationError, InvocationError};
@@ -62,8 +76,7 @@ const DEFAULT_FILE_NAME: &'static str = "tg_backup.session";
 
 pub async fn start_backup(opts: Opts, long_messages_results: Vec<ChannelsStateInfo>) {
     // Start auth subcommand
-    if let Some(auth_command) = opts.auth {
-        let SubCommand::Auth(auth_data) = auth_command;
+    if let Some(SubCommand::Auth(auth_data)) = opts.subcommand {
         auth(auth_data.session_file_dir, auth_data.session_file_name).await;
         return;
     }
@@ -78,6 +91,38 @@ pub async fn start_backup(opts: Opts, long_messages_results: Vec<ChannelsStateIn
         return;
     }
 
+    // Start list-chats subcommand
+    if let Some(SubCommand::ListChats(list_data)) = opts.subcommand {
+        let output_dir = path_or_default_output(&opts.output);
+        let _ = fs::create_dir_all(output_dir.as_path());
+        init_logs(&output_dir, opts.keep_last_n_logs, opts.panic_to_stderr);
+        let client = get_connection(&session_file).await;
+        list_chats(&client, output_dir.as_path(), list_data.format).await;
+        return;
+    }
+
+    // Check date range
+    if let Err(e) = check_date_range(opts.since, opts.until) {
+        if !opts.quiet {
+            println!("{}", e);
+        }
+        return;
+    }
+
+    // Load per-chat rules
+    let rules = match &opts.rules_file {
+        Some(path) => match load_rules(path) {
+            Ok(rules) => rules,
+            Err(e) => {
+                if !opts.quiet {
+                    println!("{}", e);
+                }
+                return;
+            }
+        },
+        None => vec![],
+    };
+
     let output_dir = path_or_default_output(&opts.output);
     // Create backup directory
     if opts.clean {
@@ -109,7 +154,20 @@ pub async fn start_backup(opts: Opts, long_messages_results: Vec<ChannelsStateIn
         output_dir.as_path(),
         opts.quiet,
         opts.file_limit,
+        opts.media_policy,
         opts.max_participants,
+        rules,
+        opts.save_web_page_previews,
+        opts.save_avatars,
+        opts.avatar_history,
+        opts.user_details,
+        opts.save_comments,
+        opts.comments_limit,
+        opts.save_admin_log,
+        opts.photo_size,
+        opts.save_thumbnails,
+        opts.since,
+        opts.until,
         opts.test,
     );
 
@@ -118,11 +176,18 @@ pub async fn start_backup(opts: Opts, long_messages_results: Vec<ChannelsStateIn
         amount_of_dialogs: None,
         total_flood_wait: 0,
         long_messages_info: long_messages_results,
+        selected_chats: vec![],
     }));
 
     // Save me
     let mut client = get_connection(&session_file).await;
     save_me(&mut client, &main_ctx).await;
+
+    // Save contacts
+    save_contacts(&client, &main_ctx.output_dir, main_ctx.date).await;
+
+    // Save dialogs with folders
+    save_dialogs(&client, &main_ctx.output_dir, main_ctx.date).await;
     drop(client);
 
     // Start backup loop
@@ -145,6 +210,11 @@ pub async fn start_backup(opts: Opts, long_messages_results: Vec<ChannelsStateIn
         }
     }
 
+    // Save ids of the selected chats
+    if let Ok(ctx) = main_mut_context.read() {
+        save_backup_info(&arc_main_ctx, ctx.selected_chats.clone());
+    }
+
     delete_lock_file(output_dir.as_path());
 
     let output_dir = path_or_default_output(&opts.output);
@@ -404,9 +474,17 @@ async fn create_connection(session_file: &Option<String>) -> Result<Client, Auth
 }
 
 async fn save_me(client: &mut Client, main_context: &MainContext) {
-    let me_result: Result<Member, InvocationError> = client.get_me().await.map(|it| it.into());
+    let me_result: Result<User, InvocationError> = client.get_me().await;
     match me_result {
-        Ok(me) => {
+        Ok(user) => {
+            let mut me: Member = (&user).into();
+            if let Some(users_details) = &main_context.users_details {
+                me.set_details(user.id(), users_details.get(client, &user).await);
+            }
+            if let Some(avatars) = &main_context.avatars {
+                let paths = avatars.save(client, &Chat::User(user.clone())).await;
+                me.set_avatars(user.id(), paths);
+            }
             let path_string = format!("{}/me.json", main_context.output_dir.display().to_string());
             let path = Path::new(path_string.as_str());
             if !path.exists() {
@@ -484,13 +562,26 @@ async fn start_iteration(
 }
 
 fn save_current_information(
-    chats: Vec<i64>,
-    excluded: Vec<i64>,
+    chats: Vec<ChatSelector>,
+    excluded: Vec<ChatSelector>,
     batch_size: i32,
     output_dir: &Path,
     quite_mode: bool,
     file_limit: Option<i32>,
+    media_policy: Vec<MediaTypeRule>,
     max_participants: i32,
+    rules: Vec<ChatRule>,
+    save_web_page_previews: bool,
+    save_avatars: bool,
+    avatar_history: bool,
+    user_details: bool,
+    save_comments: bool,
+    comments_limit: Option<usize>,
+    save_admin_log: bool,
+    photo_size: PhotoSizePolicy,
+    save_thumbnails: bool,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
     test: bool,
 ) -> MainContext {
     let loading_chats = if chats.is_empty() { None } else { Some(chats) };
@@ -500,8 +591,20 @@ fn save_current_information(
         batch_size,
         output_dir.clone().to_path_buf(),
         quite_mode,
-        file_limit.map(|x| x * 1024 * 1024),
+        MediaPolicy::new(file_limit.map(|x| x as i64 * 1024 * 1024), &media_policy),
         max_participants,
+        rules,
+        save_web_page_previews,
+        save_avatars,
+        avatar_history,
+        user_details,
+        save_comments,
+        comments_limit,
+        save_admin_log,
+        photo_size,
+        save_thumbnails,
+        since,
+        until,
         test,
     );
 
@@ -520,15 +623,29 @@ fn save_current_information(
         }
     }
 
+    save_backup_info(&main_context, vec![]);
+    main_context
+}
+
+fn save_backup_info(main_ctx: &MainContext, selected_chats: Vec<i64>) {
     let back_up_info = BackUpInfo::init(
-        main_context.date.clone(),
-        main_context.included_chats.clone(),
-        main_context.excluded_chats.clone(),
-        main_context.batch_size,
+        main_ctx.date.clone(),
+        main_ctx.included_chats.clone(),
+        main_ctx.excluded_chats.clone(),
+        main_ctx.batch_size,
+        selected_chats,
     );
-    let file = File::create(path).unwrap();
+    let file = File::create(main_ctx.output_dir.join("backup.json")).unwrap();
     serde_json::to_writer_pretty(&file, &back_up_info).unwrap();
-    main_context
+}
+
+/// Remember the chat as a saved one for the backup info
+fn record_selected_chat(main_mut_ctx: &Arc<RwLock<MainMutContext>>, chat_id: i64) {
+    if let Ok(mut ctx) = main_mut_ctx.write() {
+        if !ctx.selected_chats.contains(&chat_id) {
+            ctx.selected_chats.push(chat_id);
+        }
+    }
 }
 
 async fn extract_dialog(
@@ -542,23 +659,31 @@ async fn extract_dialog(
     let chat_id = chat.id();
     let chat_name = chat.name();
 
-    if let Some(chats) = main_ctx.included_chats.as_ref() {
-        if !chats.contains(&chat_id) {
-            return Ok(());
-        }
+    let included_chats = main_ctx.included_chats.as_deref().unwrap_or(&[]);
+    if !is_selected(included_chats, &main_ctx.excluded_chats, &chat) {
+        return Ok(());
     }
 
-    if main_ctx.excluded_chats.contains(&chat_id) {
+    let settings = ChatSettings::of(&chat, &main_ctx);
+    // Dates of the rules file may be combined with the dates of the command line
+    if let Err(e) = check_date_range(settings.since, settings.until) {
+        log::error!("Skip chat. name: {} id: {}. {}", chat_name, chat_id, e);
         return Ok(());
     }
 
-    if let Ok(ctx) = main_mut_ctx.read() {
-        if ctx.already_finished.contains(&chat_id) {
-            return Ok(());
-        }
+    let already_finished = match main_mut_ctx.read() {
+        Ok(ctx) => ctx.already_finished.contains(&chat_id),
+        Err(_) => false,
+    };
+    if already_finished {
+        // The chat was saved by the interrupted run
+        record_selected_chat(&main_mut_ctx, chat_id);
+        return Ok(());
     }
 
-    if chat.skip_backup() {
+    // Channels are saved only if they are explicitly selected
+    let explicitly_selected = included_chats.iter().any(|selector| selector.matches(chat));
+    if chat.skip_backup() && !explicitly_selected {
         return Ok(());
     }
 
@@ -574,14 +699,14 @@ async fn extract_dialog(
                     "Skip chat. name: {} id: {} because it has more than {} participants",
                     chat_name,
                     chat_id,
-                    main_ctx.max_participants
+                    settings.max_participants
                 );
                 return Ok(());
             }
         }
     }
     let chat_participants = chat.members(&client).await.iter().count() as i32;
-    if main_ctx.max_participants >= 0 && chat_participants > main_ctx.max_participants {
+    if settings.max_participants >= 0 && chat_participants > settings.max_participants {
         if let Ok(mut ctx) = main_mut_ctx.write() {
             let item = ctx
                 .long_messages_info
@@ -600,7 +725,7 @@ async fn extract_dialog(
             "Skip chat. name: {} id: {} because it has more than {} participants. Asked for permission to save",
             chat_name,
             chat_id,
-            main_ctx.max_participants
+            settings.max_participants
         );
                     return Ok(());
                 }
@@ -608,6 +733,8 @@ async fn extract_dialog(
         }
     }
 
+    record_selected_chat(&main_mut_ctx, chat_id);
+
     let visual_id = chat.visual_id();
 
     log::info!("Saving chat. name: {} id: {}", chat_name, chat_id);
@@ -631,7 +758,7 @@ async fn extract_dialog(
         let result: Result<Vec<MessageInfo>, Error> = serde_json::from_reader(file);
         match result {
             Ok(existing_data) => {
-                if existing_data.len() as i32 >= main_ctx.batch_size {
+                if existing_data.len() as i32 >= settings.batch_size {
                     vec![]
                 } else {
                     existing_data
@@ -650,20 +777,29 @@ async fn extract_dialog(
         vec![]
     };
 
-    let mut chat_ctx = ChatContext::init(chat_path, visual_id.clone(), existing_data);
+    let mut chat_ctx = ChatContext::init(chat_path, visual_id.clone(), existing_data, &settings);
     chat_ctx.initial_file = latest_file.map(|x| x.path());
 
-    let mut start_loading_time = main_ctx.date.clone();
-    let mut end_loading_time: Option<DateTime<Utc>> = None;
+    // Messages are loaded from the newest to the oldest ones
+    let since = settings.since;
+    let until = settings
+        .until
+        .map_or(main_ctx.date, |until| until.min(main_ctx.date));
+    let mut start_loading_time = until;
+    let mut end_loading_time: Option<DateTime<Utc>> = since;
     let mut last_loaded_id: Option<i32> = None;
-    let mut counter = chat_ctx.accumulator_counter * main_ctx.batch_size;
-    let mut global_loading_from = main_ctx.date.clone();
+    let mut counter = chat_ctx.accumulator_counter * settings.batch_size;
+    let mut global_loading_from = until;
+    let mut global_loading_since = since;
     let mut amount_of_already_loaded_messages: usize = 0;
 
     let in_progress = InProgress::create(chat_path);
+    let open_polls = OpenPolls::create(chat_path);
+    let mut name_history = vec![];
     if info_file_path.exists() {
         let file = BufReader::new(File::open(&info_file_path).unwrap());
         let chat_info: ChatInfo = serde_json::from_reader(file).unwrap();
+        name_history = chat_info.previous_name_history();
         if in_progress.exists() {
             log::info!("Loading data from in_progress file");
             let in_progress_data = in_progress.read_data();
@@ -672,11 +808,49 @@ async fn extract_dialog(
             chat_ctx.accumulator_counter = in_progress_data.accumulator_counter;
             chat_ctx.file_issue = in_progress_data.file_issue;
             chat_ctx.file_issue_count = in_progress_data.file_issue_count;
+            chat_ctx.continue_from = in_progress_data.continue_from;
+            chat_ctx.continue_until = in_progress_data.continue_until;
             counter = in_progress_data.messages_counter;
             last_loaded_id = in_progress_data.last_loaded_id;
             global_loading_from = chat_info.loaded_up_to;
+            global_loading_since = chat_info.loaded_since;
         } else {
-            end_loading_time = Some(chat_info.loaded_up_to);
+            // The saved messages should stay a continuous range, so newer messages are always
+            //   loaded down to the saved ones and `since` bounds only the older messages.
+            let load_newer = until > chat_info.loaded_up_to;
+            let load_older = match (chat_info.loaded_since, since) {
+                (Some(loaded_since), Some(since)) => since < loaded_since,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            global_loading_from = until.max(chat_info.loaded_up_to);
+            global_loading_since = if load_older {
+                since
+            } else {
+                chat_info.loaded_since
+            };
+            match (load_newer, load_older) {
+                (true, true) => {
+                    end_loading_time = Some(chat_info.loaded_up_to);
+                    chat_ctx.continue_from = chat_info.loaded_since;
+                    chat_ctx.continue_until = since;
+                }
+                (false, true) => {
+                    start_loading_time = chat_info.loaded_since.unwrap();
+                    end_loading_time = since;
+                    // Older messages go to new files, the newest saved file stays untouched
+                    chat_ctx.messages_accumulator.clear();
+                    chat_ctx.accumulator_counter = 0;
+                    chat_ctx.initial_file = None;
+                    counter = 0;
+                }
+                (_, false) => {
+                    if !load_newer {
+                        start_loading_time = chat_info.loaded_up_to;
+                    }
+                    end_loading_time = Some(chat_info.loaded_up_to);
+                }
+            }
             amount_of_already_loaded_messages = chat_info.total_messages;
             let info = InProgressInfo::create(
                 start_loading_time,
@@ -710,17 +884,43 @@ async fn extract_dialog(
     let total_messages = iter_messages.total().await.unwrap_or(0);
     let amount_of_messages_to_load = total_messages - amount_of_already_loaded_messages;
 
+    // Save avatars of the chat
+    let chat_avatars = match (&main_ctx.avatars, chat) {
+        (Some(avatars), Chat::Group(_)) | (Some(avatars), Chat::Channel(_)) => {
+            avatars.save(&client, chat).await
+        }
+        _ => vec![],
+    };
+
     // Save info file
+    let mut chat_info = chat_to_info(&chat, global_loading_from, total_messages, chat_avatars);
+    chat_info.loaded_since = global_loading_since;
+    chat_info.details = chat.details(&client).await;
+    chat_info.set_name_history(name_history, main_ctx.date);
     let info_file = File::create(info_file_path).unwrap();
-    serde_json::to_writer_pretty(
-        &info_file,
-        &chat_to_info(&chat, global_loading_from, total_messages),
-    )
-    .unwrap();
+    serde_json::to_writer_pretty(&info_file, &chat_info).unwrap();
 
     // Save members
-    let members_await = chat.members(&client);
-    let members = members_await.await;
+    let users = chat.users(&client).await;
+    let mut members = chat.to_members(&users);
+    let statuses = main_ctx.users_details.as_ref().map(|_| statuses(&users));
+    if let Some(users_details) = &main_ctx.users_details {
+        for user in &users {
+            let details = users_details.get(&client, user).await;
+            members
+                .iter_mut()
+                .for_each(|member| member.set_details(user.id(), details.clone()));
+        }
+    }
+    if let Some(avatars) = &main_ctx.avatars {
+        for user in users {
+            let user_id = user.id();
+            let paths = avatars.save(&client, &Chat::User(user)).await;
+            members
+                .iter_mut()
+                .for_each(|member| member.set_avatars(user_id, paths.clone()));
+        }
+    }
     let members_folder = chat_path.join("members");
     let _ = fs::create_dir(&members_folder);
     let members_path = members_folder.join("members.json");
@@ -735,6 +935,24 @@ async fn extract_dialog(
             serde_json::to_writer_pretty(&members_file, &members).unwrap();
         }
     }
+    // Statuses change on every run, so they are not a part of the members file
+    if let Some(statuses) = statuses {
+        let statuses_file = File::create(members_folder.join("statuses.json")).unwrap();
+        serde_json::to_writer_pretty(&statuses_file, &statuses).unwrap();
+    }
+
+    // Save scheduled messages
+    save_scheduled(&client, chat, chat_path, &mut chat_ctx, &main_ctx).await;
+
+    // Save admin log
+    if main_ctx.save_admin_log && is_admin(chat) {
+        save_admin_log(&client, chat, chat_path, main_ctx.date).await;
+    }
+
+    chat_ctx.add_users(&members);
+    chat_ctx
+        .names
+        .insert(PeerInfo::from(chat), chat_name.to_string());
 
     // Initialize progress bar
     let mut pb = ProgressBar::new(amount_of_messages_to_load as u64);
@@ -761,7 +979,24 @@ async fn extract_dialog(
                 let message_id = message.id();
                 if let Some(end_time) = end_loading_time {
                     if message_date < end_time {
+                        // Skip the already saved messages and continue with the older ones
+                        if let Some(continue_from) = chat_ctx.continue_from.take() {
+                            log::info!("Continue loading from {}", continue_from);
+                            // Newer and older messages are not saved into the same file
+                            chat_ctx.force_drop_messages(&main_ctx);
+                            end_loading_time = chat_ctx.continue_until.take();
+                            start_loading_time = continue_from;
+                            last_loaded_id = None;
+                            last_message = None;
+                            iter_messages = client
+                                .iter_messages(dialog.chat())
+                                .offset_date(continue_from.timestamp() as i32);
+                            continue;
+                        }
                         chat_ctx.force_drop_messages(&main_ctx);
+                        open_polls
+                            .refresh(&client, chat, chat_path, &chat_ctx.open_polls)
+                            .await;
                         in_progress.remove_file();
                         if let Ok(mut ctx) = main_mut_ctx.write() {
                             ctx.already_finished.push(chat_id);
@@ -773,7 +1008,7 @@ async fn extract_dialog(
                         return Ok(());
                     }
                 }
-                let saving_result = save_message(&message, &mut chat_ctx, &main_ctx).await;
+                let saving_result = save_message(&client, &message, &mut chat_ctx, &main_ctx).await;
                 if let Err(_) = saving_result {
                     log::error!("Error while loading");
                     if let Some(pb) = chat_ctx.pb.as_mut() {
@@ -794,6 +1029,7 @@ async fn extract_dialog(
                     in_progress.write_data(&info);
                     log::info!("Force drop messages. Counter: {}", info.messages_counter);
                     chat_ctx.force_drop_messages(&main_ctx);
+                    open_polls.add(&chat_ctx.open_polls);
                     return Err(());
                 }
 
@@ -828,6 +1064,9 @@ async fn extract_dialog(
             }
             Ok(None) => {
                 chat_ctx.force_drop_messages(&main_ctx);
+                open_polls
+                    .refresh(&client, chat, chat_path, &chat_ctx.open_polls)
+                    .await;
                 in_progress.remove_file();
                 if let Ok(mut ctx) = main_mut_ctx.write() {
                     ctx.already_finished.push(chat_id);
@@ -883,6 +1122,7 @@ async fn extract_dialog(
     }
 
     chat_ctx.force_drop_messages(&main_ctx);
+    open_polls.add(&chat_ctx.open_polls);
 
     // if let Some(pb) = chat_ctx.pb.as_mut() {
     //     pb.finish();
@@ -949,10 +1189,80 @@ fn compare_by_names(first_name: &str, second_name: &str) -> Ordering {
 }
 
 async fn save_message(
+    client: &Client,
     message: &Message,
     chat_ctx: &mut ChatContext,
     main_ctx: &Arc<MainContext>,
 ) -> Result<(), ()> {
+    let message_text = message.text();
+    let attachment = save_attachment(client, message, chat_ctx, main_ctx).await?;
+    if let Some(Attachment::Poll(poll)) = &attachment {
+        if !poll.closed {
+            chat_ctx.open_polls.push(message.id());
+        }
+    }
+
+    if let Some(sender) = message.sender() {
+        chat_ctx
+            .names
+            .insert(PeerInfo::from(&sender), sender.name().to_string());
+        if let Chat::User(user) = sender {
+            chat_ctx.users.entry(user.id()).or_insert(user.into());
+        }
+    }
+    let action = get_action(message).map(|mut action| {
+        action.resolve_members(&chat_ctx.users);
+        action
+    });
+    log::debug!("Loading message {}", message_text);
+    let mut forwarded_from = message.fwd_from();
+    if let Some(forward_info) = forwarded_from.as_mut() {
+        forward_info.resolve_name(&chat_ctx.names);
+    }
+    let comments = if main_ctx.save_comments && message.reply_count().unwrap_or(0) > 0 {
+        if let Chat::Channel(_) = message.chat() {
+            log::debug!("Loading comments {}", message_text);
+            let comments_type = chat_ctx.types.get(COMMENTS).unwrap();
+            let saved = save_comments(
+                client,
+                &message.chat(),
+                message.id(),
+                comments_type,
+                main_ctx.comments_limit,
+            )
+            .await;
+            match saved {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    log::error!("Cannot load comments of {}: {}", message.id(), e);
+                    None
+                }
+            }
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    let mut message = msg_to_info(&message, attachment, action, forwarded_from);
+    message.comments = comments;
+    if let Some(via_bot) = message.via_bot.as_mut() {
+        via_bot.name = chat_ctx
+            .names
+            .get(&PeerInfo::User { id: via_bot.id })
+            .cloned();
+    }
+    chat_ctx.messages_accumulator.push(message);
+    Ok(())
+}
+
+/// Download the media of the message according to the media policy of the chat
+pub(crate) async fn save_attachment(
+    client: &Client,
+    message: &Message,
+    chat_ctx: &mut ChatContext,
+    main_ctx: &Arc<MainContext>,
+) -> Result<Option<Attachment>, ()> {
     let message_text = message.text();
     let types = &chat_ctx.types;
     let option_photo = message.photo();
@@ -961,74 +1271,27 @@ async fn save_message(
     let option_geo_live = message.geo_live();
     let option_dice = message.dice();
     let option_contact = message.contact();
+    let option_poll = message.poll();
+    let option_web_page = message.web_page();
     let attachment = if let Some(photo) = option_photo {
         if let Some(pb) = chat_ctx.pb.as_mut() {
             pb.message(format!("Loading {} [photo   ] ", chat_ctx.visual_id.as_str()).as_str());
         }
         log::debug!("Loading photo {}", message_text);
-        let current_type = types.get(PHOTO).unwrap();
-        let photo_id = photo.id();
-        if let Some(id) = photo_id {
-            let file_name = format!("{}@photo.jpg", id);
-            let photos_path = current_type.path().join(file_name.as_str());
-            let photo_size = photo.thumbs().largest().unwrap().size();
-
-            if main_ctx.max_attachment_size_in_bytes.is_some()
-                && main_ctx.max_attachment_size_in_bytes.unwrap() < photo_size as i32
-            {
-                Some(TooLarge {
-                    size: photo_size as i32,
-                })
-            } else {
-                let downloaded = photo
-                    .thumbs()
-                    .largest()
-                    .unwrap()
-                    .download(photos_path)
-                    .await;
-                let photo_path = format!("../{}/{}", current_type.folder, file_name);
-                let attachment = Attachment::Photo(FileInfo {
-                    id,
-                    path: photo_path,
-                });
-                if let Err(e) = downloaded {
-                    if chat_ctx.file_issue == id {
-                        chat_ctx.file_issue_count += 1;
-                        if chat_ctx.file_issue_count > 5 {
-                            log::error!("Cannot download photo, no more attempts {}", e);
-                            Some(Attachment::Error(format!("Cannot load: {}", e)))
-                        } else {
-                            log::warn!(
-                                "Cannot download photo, attempt {}, error: {}",
-                                chat_ctx.file_issue_count,
-                                e
-                            );
-                            return Err(());
-                        }
-                    } else {
-                        chat_ctx.file_issue = id;
-                        chat_ctx.file_issue_count = 0;
-                        log::warn!("Cannot download photo, first attempt: {}", e);
-                        return Err(());
-                    }
-                } else {
-                    Some(attachment)
-                }
-            }
-        } else {
-            Some(PhotoExpired)
-        }
+        Some(save_photo(&photo, chat_ctx, main_ctx).await?)
     } else if let Some(mut doc) = option_document {
-        if main_ctx.max_attachment_size_in_bytes.is_some()
-            && main_ctx.max_attachment_size_in_bytes.unwrap() < doc.size() as i32
-        {
-            Some(TooLarge {
-                size: doc.size() as i32,
-            })
+        let media_type = MediaType::of_document(&doc);
+        if let Err(reason) = chat_ctx.media_policy.check(media_type, doc.size() as i64) {
+            // Disabled media types are not downloaded at all, even as thumbnails
+            let thumbnail = match reason {
+                SkipReason::Disabled => None,
+                SkipReason::TooLarge => save_thumbnail(&doc, chat_ctx, main_ctx).await,
+            };
+            Some(reason.to_attachment(media_type, doc.size() as i64, thumbnail))
         } else {
             let doc_id = doc.id();
             let doc_name = doc.name().to_string();
-            let (attachment, file_path) = if doc.is_round_message() {
+            let (mut attachment, file_path) = if doc.is_round_message() {
                 if let Some(pb) = chat_ctx.pb.as_mut() {
                     pb.message(
                         format!("Loading {} [round   ] ", chat_ctx.visual_id.as_str()).as_str(),
@@ -1041,10 +1304,7 @@ async fn save_message(
                 let file_name = current_type.format(file_name);
                 let file_path = current_type.path().join(file_name.as_str());
                 let att_path = format!("../{}/{}", current_type.folder, file_name);
-                let attachment = Attachment::Round(FileInfo {
-                    id: doc_id,
-                    path: att_path,
-                });
+                let attachment = Attachment::Round(FileInfo::from_document(att_path, &doc));
                 (attachment, file_path)
             } else if doc.is_voice_message() {
                 if let Some(pb) = chat_ctx.pb.as_mut() {
@@ -1059,10 +1319,7 @@ async fn save_message(
                 let file_name = current_type.format(file_name);
                 let file_path = current_type.path().join(file_name.as_str());
                 let att_path = format!("../{}/{}", current_type.folder, file_name);
-                let attachment = Attachment::Voice(FileInfo {
-                    id: doc_id,
-                    path: att_path,
-                });
+                let attachment = Attachment::Voice(FileInfo::from_document(att_path, &doc));
                 (attachment, file_path)
             } else {
                 if let Some(pb) = chat_ctx.pb.as_mut() {
@@ -1077,13 +1334,17 @@ async fn save_message(
                 let file_name = current_type.format(file_name);
                 let file_path = current_type.path().join(file_name.as_str());
                 let photo_path = format!("../{}/{}", current_type.folder, file_name);
-                let attachment = Attachment::File(FileInfo {
-                    id: doc_id,
-                    path: photo_path,
-                });
+                let attachment = Attachment::File(FileInfo::from_document(photo_path, &doc));
                 (attachment, file_path)
             };
 
+            let thumbnail = save_thumbnail(&doc, chat_ctx, main_ctx).await;
+            if let Attachment::File(info) | Attachment::Voice(info) | Attachment::Round(info) =
+                &mut attachment
+            {
+                info.thumbnail = thumbnail;
+            }
+
             // TODO handle file migrate
             let downloaded = doc.download(&file_path).await;
             if let Err(e) = downloaded {
@@ -1113,31 +1374,122 @@ async fn save_message(
         Some(Attachment::Dice(dice))
     } else if let Some(contact) = option_contact {
         Some(Attachment::Contact(contact))
+    } else if let Some(poll) = option_poll {
+        Some(Attachment::Poll(poll))
+    } else if let Some(mut web_page) = option_web_page {
+        if main_ctx.save_web_page_previews {
+            if let Some(photo) = message.web_page_photo(client) {
+                if let Some(pb) = chat_ctx.pb.as_mut() {
+                    pb.message(
+                        format!("Loading {} [photo   ] ", chat_ctx.visual_id.as_str()).as_str(),
+                    );
+                }
+                log::debug!("Loading web page preview {}", message_text);
+                web_page.photo = Some(Box::new(save_photo(&photo, chat_ctx, main_ctx).await?));
+            }
+        }
+        Some(Attachment::WebPage(web_page))
     } else {
-        None
+        message.other_media()
     };
+    Ok(attachment)
+}
 
-    let action = get_action(message);
-    log::debug!("Loading message {}", message_text);
-    let message = msg_to_info(&message, attachment, action);
-    chat_ctx.messages_accumulator.push(message);
-    Ok(())
+async fn save_photo(
+    photo: &Photo,
+    chat_ctx: &mut ChatContext,
+    main_ctx: &Arc<MainContext>,
+) -> Result<Attachment, ()> {
+    let current_type = chat_ctx.types.get(PHOTO).unwrap();
+    let photo_id = photo.id();
+    let sizes = photo.sizes(&main_ctx.photo_size);
+    if let (Some(id), Some(main_size)) = (photo_id, sizes.first()) {
+        let file_name = format!("{}@photo.jpg", id);
+        let photos_path = current_type.path().join(file_name.as_str());
+        let photo_size = main_size.size();
+
+        if let Err(reason) = chat_ctx
+            .media_policy
+            .check(MediaType::Photo, photo_size as i64)
+        {
+            Ok(reason.to_attachment(MediaType::Photo, photo_size as i64, None))
+        } else {
+            let downloaded = main_size.download(photos_path).await;
+            let photo_path = format!("../{}/{}", current_type.folder, file_name);
+            let mut file_info = FileInfo::from_photo(id, photo_path, photo, main_size);
+            for size in sizes.iter().skip(1) {
+                let file_name = format!("{}@photo_{}.jpg", id, size.photo_type());
+                let size_path = current_type.path().join(file_name.as_str());
+                if let Err(e) = size.download(size_path).await {
+                    log::warn!(
+                        "Cannot download size {} of photo {}: {}",
+                        size.photo_type(),
+                        id,
+                        e
+                    );
+                    continue;
+                }
+                file_info
+                    .sizes
+                    .push(format!("../{}/{}", current_type.folder, file_name));
+            }
+            let attachment = Attachment::Photo(file_info);
+            if let Err(e) = downloaded {
+                if chat_ctx.file_issue == id {
+                    chat_ctx.file_issue_count += 1;
+                    if chat_ctx.file_issue_count > 5 {
+                        log::error!("Cannot download photo, no more attempts {}", e);
+                        Ok(Attachment::Error(format!("Cannot load: {}", e)))
+                    } else {
+                        log::warn!(
+                            "Cannot download photo, attempt {}, error: {}",
+                            chat_ctx.file_issue_count,
+                            e
+                        );
+                        Err(())
+                    }
+                } else {
+                    chat_ctx.file_issue = id;
+                    chat_ctx.file_issue_count = 0;
+                    log::warn!("Cannot download photo, first attempt: {}", e);
+                    Err(())
+                }
+            } else {
+                Ok(attachment)
+            }
+        }
+    } else {
+        Ok(PhotoExpired)
+    }
+}
+
+/// Save the largest thumbnail of the document if thumbnails are enabled.
+/// Returns path to the saved thumbnail.
+async fn save_thumbnail(
+    doc: &Document,
+    chat_ctx: &ChatContext,
+    main_ctx: &Arc<MainContext>,
+) -> Option<String> {
+    if !main_ctx.save_thumbnails {
+        return None;
+    }
+    let thumbs = doc.thumbs();
+    let thumb = thumbs.largest()?;
+    let current_type = chat_ctx.types.get(THUMBNAIL).unwrap();
+    let file_name = current_type.format(format!("{}@thumb", doc.id()));
+    let downloaded = thumb
+        .download(current_type.path().join(file_name.as_str()))
+        .await;
+    if let Err(e) = downloaded {
+        log::warn!("Cannot download thumbnail of {}: {}", doc.id(), e);
+        return None;
+    }
+    Some(format!("../{}/{}", current_type.folder, file_name))
 }
 
 fn get_action(message: &Message) -> Option<Action> {
     let action: &tl::enums::MessageAction = message.action()?;
-    let result = match action {
-        tl::enums::MessageAction::PhoneCall(call) => call.into(),
-        tl::enums::MessageAction::ChatCreate(chat_create) => chat_create.into(),
-        tl::enums::MessageAction::ChatEditTitle(edit_title) => edit_title.into(),
-        tl::enums::MessageAction::GroupCall(group_call) => group_call.into(),
-        tl::enums::MessageAction::InviteToGroupCall(invite_to_group_call) => {
-            invite_to_group_call.into()
-        }
-        tl::enums::MessageAction::HistoryClear => Action::HistoryClear {},
-        _ => Action::UnsupportedByTgBackup(format!("{:?}", action)),
-    };
-    Some(result)
+    Some(Action::from_tl(action, message.reply_to_message_id()))
 }
 
 #[cfg(test)]
@@ -0,0 +1,143 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::attachment_type::AttachmentType;
+use crate::entities::EntityInfo;
+use crate::types::{peer_names, to_date, PeerInfo};
+use chrono::{DateTime, Utc};
+use grammers_client::types::Chat;
+use grammers_client::Client;
+use grammers_mtsender::InvocationError;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+
+const PAGE_SIZE: i32 = 100;
+
+/// Comments to a channel post. They are stored in the discussion group linked to the channel.
+#[derive(Serialize, Deserialize)]
+pub struct CommentsInfo {
+    pub post_id: i32,
+    pub discussion_chat: Option<PeerInfo>,
+    pub comments: Vec<CommentInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommentInfo {
+    pub id: i32,
+    pub date: DateTime<Utc>,
+    pub text: String,
+    pub entities: Vec<EntityInfo>,
+    pub sender_id: Option<i64>,
+    pub sender_name: Option<String>,
+    pub reply_to_message_id: Option<i32>,
+    pub edit_date: Option<DateTime<Utc>>,
+}
+
+/// Load comments of the channel post and save them into the comments folder.
+/// Returns path to the saved file.
+pub async fn save_comments(
+    client: &Client,
+    channel: &Chat,
+    post_id: i32,
+    comments_type: &AttachmentType,
+    limit: Option<usize>,
+) -> Result<String, InvocationError> {
+    let mut comments = vec![];
+    let mut discussion_chat = None;
+    let mut offset_id = 0;
+    loop {
+        let request = tl::functions::messages::GetReplies {
+            peer: channel.pack().to_input_peer(),
+            msg_id: post_id,
+            offset_id,
+            offset_date: 0,
+            add_offset: 0,
+            limit: PAGE_SIZE,
+            max_id: 0,
+            min_id: 0,
+            hash: 0,
+        };
+        let (messages, users, chats) = match client.invoke(&request).await? {
+            tl::enums::messages::Messages::Messages(data) => {
+                (data.messages, data.users, data.chats)
+            }
+            tl::enums::messages::Messages::Slice(data) => (data.messages, data.users, data.chats),
+            tl::enums::messages::Messages::ChannelMessages(data) => {
+                (data.messages, data.users, data.chats)
+            }
+            tl::enums::messages::Messages::NotModified(_) => (vec![], vec![], vec![]),
+        };
+        let names = peer_names(&users, &chats);
+        let page_size = messages.len();
+
+        for message in messages {
+            if let tl::enums::Message::Message(message) = message {
+                offset_id = message.id;
+                if discussion_chat.is_none() {
+                    discussion_chat = Some((&message.peer_id).into());
+                }
+                comments.push(to_comment(message, &names));
+            }
+        }
+
+        let limit_reached = limit.map_or(false, |limit| comments.len() >= limit);
+        if limit_reached || page_size < PAGE_SIZE as usize {
+            break;
+        }
+    }
+    if let Some(limit) = limit {
+        comments.truncate(limit);
+    }
+    comments.sort_by_key(|it| it.date);
+
+    let info = CommentsInfo {
+        post_id,
+        discussion_chat,
+        comments,
+    };
+    let file_name = comments_type.format(post_id.to_string());
+    let file = File::create(comments_type.path().join(file_name.as_str())).unwrap();
+    serde_json::to_writer_pretty(&file, &info).unwrap();
+    Ok(format!("../{}/{}", comments_type.folder, file_name))
+}
+
+fn to_comment(message: tl::types::Message, names: &HashMap<PeerInfo, String>) -> CommentInfo {
+    let sender = message.from_id.as_ref().map(PeerInfo::from);
+    let reply_to_message_id = match &message.reply_to {
+        Some(tl::enums::MessageReplyHeader::Header(header)) => header.reply_to_msg_id,
+        _ => None,
+    };
+    CommentInfo {
+        id: message.id,
+        date: to_date(message.date),
+        entities: message
+            .entities
+            .as_ref()
+            .map(|entities| entities.iter().map(|it| it.into()).collect())
+            .unwrap_or(vec![]),
+        text: message.message,
+        sender_id: sender.as_ref().map(PeerInfo::id),
+        sender_name: sender.and_then(|peer| names.get(&peer).cloned()),
+        reply_to_message_id,
+        edit_date: message.edit_date.map(to_date),
+    }
+}
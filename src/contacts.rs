@@ -0,0 +1,185 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use chrono::{DateTime, Utc};
+use grammers_client::Client;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+const FILE_NAME: &'static str = "contacts.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ContactRecord {
+    pub id: i64,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub phone: Option<String>,
+    pub username: Option<String>,
+    pub mutual: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct ContactsChange {
+    pub date: DateTime<Utc>,
+    pub added: Vec<ContactRecord>,
+    pub removed: Vec<ContactRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ContactsInfo {
+    pub date: DateTime<Utc>,
+    pub contacts: Vec<ContactRecord>,
+    /// Contacts that were added or removed between the runs
+    pub changes: Vec<ContactsChange>,
+}
+
+pub async fn save_contacts(client: &Client, output_dir: &Path, date: DateTime<Utc>) {
+    let request = tl::functions::contacts::GetContacts { hash: 0 };
+    let contacts = match client.invoke(&request).await {
+        Ok(tl::enums::contacts::Contacts::Contacts(data)) => to_records(data),
+        Ok(tl::enums::contacts::Contacts::NotModified) => return,
+        Err(e) => {
+            log::error!("Cannot load contacts: {}", e);
+            return;
+        }
+    };
+
+    let path = output_dir.join(FILE_NAME);
+    let previous: Option<ContactsInfo> = File::open(&path)
+        .ok()
+        .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok());
+
+    let info = match previous {
+        Some(mut previous) => {
+            let change = diff(&previous.contacts, &contacts, date);
+            if let Some(change) = change {
+                previous.changes.push(change);
+            }
+            ContactsInfo {
+                date,
+                contacts,
+                changes: previous.changes,
+            }
+        }
+        None => ContactsInfo {
+            date,
+            contacts,
+            changes: vec![],
+        },
+    };
+
+    let file = File::create(path).unwrap();
+    serde_json::to_writer_pretty(&file, &info).unwrap();
+}
+
+fn to_records(data: tl::types::contacts::Contacts) -> Vec<ContactRecord> {
+    let users: HashMap<i64, tl::types::User> = data
+        .users
+        .into_iter()
+        .filter_map(|user| match user {
+            tl::enums::User::User(user) => Some((user.id, user)),
+            tl::enums::User::Empty(_) => None,
+        })
+        .collect();
+    data.contacts
+        .iter()
+        .map(|tl::enums::Contact::Contact(contact)| {
+            let user = users.get(&contact.user_id);
+            ContactRecord {
+                id: contact.user_id,
+                first_name: user.and_then(|it| it.first_name.clone()),
+                last_name: user.and_then(|it| it.last_name.clone()),
+                phone: user.and_then(|it| it.phone.clone()),
+                username: user.and_then(|it| it.username.clone()),
+                mutual: contact.mutual,
+            }
+        })
+        .collect()
+}
+
+fn diff(
+    previous: &Vec<ContactRecord>,
+    current: &Vec<ContactRecord>,
+    date: DateTime<Utc>,
+) -> Option<ContactsChange> {
+    let added: Vec<ContactRecord> = current
+        .iter()
+        .filter(|contact| !previous.iter().any(|it| it.id == contact.id))
+        .cloned()
+        .collect();
+    let removed: Vec<ContactRecord> = previous
+        .iter()
+        .filter(|contact| !current.iter().any(|it| it.id == contact.id))
+        .cloned()
+        .collect();
+    if added.is_empty() && removed.is_empty() {
+        None
+    } else {
+        Some(ContactsChange {
+            date,
+            added,
+            removed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn contact(id: i64) -> ContactRecord {
+        ContactRecord {
+            id,
+            first_name: Some(String::from("Alex")),
+            last_name: None,
+            phone: None,
+            username: None,
+            mutual: false,
+        }
+    }
+
+    #[test]
+    fn no_changes() {
+        let contacts = vec![contact(1), contact(2)];
+        assert_eq!(None, diff(&contacts, &contacts, Utc::now()));
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let date = Utc::now();
+        let change = diff(
+            &vec![contact(1), contact(2)],
+            &vec![contact(2), contact(3)],
+            date,
+        );
+        assert_eq!(
+            Some(ContactsChange {
+                date,
+                added: vec![contact(3)],
+                removed: vec![contact(1)],
+            }),
+            change
+        );
+    }
+}
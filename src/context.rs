@@ -23,8 +23,14 @@ use std::fs::File;
 use std::path::{Path, PathBuf};
 
 use crate::attachment_type::AttachmentType;
+use crate::avatars::Avatars;
 use crate::companion::ChannelsStateInfo;
-use crate::types::MessageInfo;
+use crate::media_policy::MediaPolicy;
+use crate::opts::PhotoSizePolicy;
+use crate::rules::{ChatRule, ChatSettings};
+use crate::selectors::ChatSelector;
+use crate::types::{album_index, user_name, Member, MessageInfo, PeerInfo};
+use crate::users::UsersDetails;
 use chrono::{DateTime, Utc};
 use pbr::ProgressBar;
 use std::fs;
@@ -35,6 +41,9 @@ pub const PHOTO: &'static str = "photo";
 pub const FILE: &'static str = "file";
 pub const ROUND: &'static str = "round";
 pub const VOICE: &'static str = "voice";
+pub const COMMENTS: &'static str = "comments";
+pub const ALBUMS: &'static str = "albums";
+pub const THUMBNAIL: &'static str = "thumbnail";
 
 pub struct MainMutContext {
     pub(crate) already_finished: Vec<i64>,
@@ -42,32 +51,67 @@ pub struct MainMutContext {
 
     pub(crate) total_flood_wait: u32,
     pub(crate) long_messages_info: Vec<ChannelsStateInfo>,
+    /// Ids of chats that were matched by chat selectors
+    pub(crate) selected_chats: Vec<i64>,
 }
 
 pub struct MainContext {
     pub(crate) date: DateTime<Utc>,
     pub(crate) date_from: Option<DateTime<Utc>>,
     pub(crate) batch_size: i32,
-    pub(crate) included_chats: Option<Vec<i64>>,
-    pub(crate) excluded_chats: Vec<i64>,
+    pub(crate) included_chats: Option<Vec<ChatSelector>>,
+    pub(crate) excluded_chats: Vec<ChatSelector>,
     pub(crate) output_dir: PathBuf,
     pub(crate) quite_mode: bool,
-    pub(crate) max_attachment_size_in_bytes: Option<i32>,
+    pub(crate) media_policy: MediaPolicy,
     pub(crate) max_participants: i32,
+    pub(crate) rules: Vec<ChatRule>,
+    pub(crate) save_web_page_previews: bool,
+    pub(crate) avatars: Option<Avatars>,
+    pub(crate) users_details: Option<UsersDetails>,
+    pub(crate) save_comments: bool,
+    pub(crate) comments_limit: Option<usize>,
+    pub(crate) save_admin_log: bool,
+    pub(crate) photo_size: PhotoSizePolicy,
+    pub(crate) save_thumbnails: bool,
+    pub(crate) since: Option<DateTime<Utc>>,
+    pub(crate) until: Option<DateTime<Utc>>,
     pub(crate) test: bool,
 }
 
 impl MainContext {
     pub fn init(
-        loading_chats: Option<Vec<i64>>,
-        excluded_chats: Vec<i64>,
+        loading_chats: Option<Vec<ChatSelector>>,
+        excluded_chats: Vec<ChatSelector>,
         batch_size: i32,
         output_dir: PathBuf,
         quite_mode: bool,
-        max_attachment_size_in_bytes: Option<i32>,
+        media_policy: MediaPolicy,
         max_participants: i32,
+        rules: Vec<ChatRule>,
+        save_web_page_previews: bool,
+        save_avatars: bool,
+        avatar_history: bool,
+        user_details: bool,
+        save_comments: bool,
+        comments_limit: Option<usize>,
+        save_admin_log: bool,
+        photo_size: PhotoSizePolicy,
+        save_thumbnails: bool,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
         test: bool,
     ) -> MainContext {
+        let avatars = if save_avatars {
+            Some(Avatars::create(&output_dir, avatar_history))
+        } else {
+            None
+        };
+        let users_details = if user_details {
+            Some(UsersDetails::create())
+        } else {
+            None
+        };
         MainContext {
             date: Utc::now(),
             date_from: None,
@@ -76,8 +120,19 @@ impl MainContext {
             excluded_chats,
             output_dir,
             quite_mode,
-            max_attachment_size_in_bytes,
+            media_policy,
             max_participants,
+            rules,
+            save_web_page_previews,
+            avatars,
+            users_details,
+            save_comments,
+            comments_limit,
+            save_admin_log,
+            photo_size,
+            save_thumbnails,
+            since,
+            until,
             test,
         }
     }
@@ -94,10 +149,30 @@ pub struct ChatContext {
     pub(crate) file_issue_count: i32,
 
     pub(crate) initial_file: Option<PathBuf>,
+
+    pub(crate) open_polls: Vec<i32>,
+
+    /// Known users of the chat. Used to resolve members that are mentioned by id only
+    pub(crate) users: HashMap<i64, Member>,
+    /// Display names of known users and chats. Users, groups and channels may have the same ids
+    pub(crate) names: HashMap<PeerInfo, String>,
+
+    /// Older messages are loaded from this date when the loading reaches the already saved messages
+    pub(crate) continue_from: Option<DateTime<Utc>>,
+    pub(crate) continue_until: Option<DateTime<Utc>>,
+
+    /// Media policy and batch size of the chat after applying the rules file
+    pub(crate) media_policy: MediaPolicy,
+    pub(crate) batch_size: i32,
 }
 
 impl ChatContext {
-    pub fn init(path: &Path, visual_id: String, initial_acc: Vec<MessageInfo>) -> ChatContext {
+    pub fn init(
+        path: &Path,
+        visual_id: String,
+        initial_acc: Vec<MessageInfo>,
+        settings: &ChatSettings,
+    ) -> ChatContext {
         let mut types = ChatContext::init_types();
         types.values_mut().for_each(|x| x.init_folder(path));
         ChatContext {
@@ -109,6 +184,30 @@ impl ChatContext {
             file_issue: 0,
             file_issue_count: 0,
             initial_file: None,
+            open_polls: vec![],
+            users: HashMap::new(),
+            names: HashMap::new(),
+            continue_from: None,
+            continue_until: None,
+            media_policy: settings.media_policy.clone(),
+            batch_size: settings.batch_size,
+        }
+    }
+
+    pub(crate) fn add_users(&mut self, members: &Vec<Member>) {
+        for member in members {
+            if let Member::User {
+                id,
+                first_name,
+                last_name,
+                ..
+            } = member
+            {
+                if let Some(name) = user_name(Some(first_name), last_name.as_deref()) {
+                    self.names.insert(PeerInfo::User { id: *id }, name);
+                }
+                self.users.insert(*id, member.clone());
+            }
         }
     }
 
@@ -134,11 +233,23 @@ impl ChatContext {
             VOICE.to_string(),
             AttachmentType::init("media/voice_messages", VOICE, Some(".ogg")),
         );
+        map.insert(
+            COMMENTS.to_string(),
+            AttachmentType::init("comments", COMMENTS, Some(".json")),
+        );
+        map.insert(
+            ALBUMS.to_string(),
+            AttachmentType::init("albums", ALBUMS, Some(".json")),
+        );
+        map.insert(
+            THUMBNAIL.to_string(),
+            AttachmentType::init("media/thumbnails", THUMBNAIL, Some(".jpg")),
+        );
         map
     }
 
     pub(crate) fn drop_messages(&mut self, main_ctx: &MainContext) -> bool {
-        if self.messages_accumulator.len() < main_ctx.batch_size as usize {
+        if self.messages_accumulator.len() < self.batch_size as usize {
             return false;
         }
         self.force_drop_messages(main_ctx);
@@ -156,6 +267,10 @@ impl ChatContext {
         // the new file is not yet written.
         if let Some(initial_file) = &self.initial_file {
             let _ = fs::remove_file(initial_file);
+            if let Some(file_name) = initial_file.file_name() {
+                let albums_path = self.types.get(ALBUMS).unwrap().path();
+                let _ = fs::remove_file(albums_path.join(file_name));
+            }
             self.initial_file = None;
         }
 
@@ -190,9 +305,18 @@ impl ChatContext {
             counter += 1;
         }
 
-        let file = File::create(file_path).unwrap();
+        let file = File::create(&file_path).unwrap();
         serde_json::to_writer_pretty(&file, &self.messages_accumulator).unwrap();
 
+        // Albums index has the same name as the file with messages
+        let albums = album_index(&self.messages_accumulator);
+        if !albums.is_empty() {
+            let albums_path = self.types.get(ALBUMS).unwrap().path();
+            let albums_file =
+                File::create(albums_path.join(file_path.file_name().unwrap())).unwrap();
+            serde_json::to_writer_pretty(&albums_file, &albums).unwrap();
+        }
+
         self.messages_accumulator.clear();
         self.accumulator_counter += 1;
     }
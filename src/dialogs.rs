@@ -0,0 +1,282 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::ext::ChatExt;
+use crate::types::ChatType;
+use chrono::{DateTime, Utc};
+use grammers_client::types::{Chat, Dialog};
+use grammers_client::Client;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+const FILE_NAME: &'static str = "dialogs.json";
+
+/// Id of the archive folder in the Telegram API
+const ARCHIVE_FOLDER_ID: i32 = 1;
+
+/// State of the dialog list at the moment of the backup. The file is rewritten on every run.
+#[derive(Serialize, Deserialize)]
+pub struct DialogsInfo {
+    pub date: DateTime<Utc>,
+    pub folders: Vec<FolderInfo>,
+    pub dialogs: Vec<DialogRecord>,
+}
+
+/// Custom chat folder with its rules
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FolderInfo {
+    pub id: i32,
+    pub title: String,
+    pub emoticon: Option<String>,
+    /// The folder is shared by an invite link
+    pub shared: bool,
+    pub contacts: bool,
+    pub non_contacts: bool,
+    pub groups: bool,
+    pub broadcasts: bool,
+    pub bots: bool,
+    pub exclude_muted: bool,
+    pub exclude_read: bool,
+    pub exclude_archived: bool,
+    pub pinned_chats: Vec<i64>,
+    pub included_chats: Vec<i64>,
+    pub excluded_chats: Vec<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DialogRecord {
+    pub id: i64,
+    pub name: String,
+    pub chat_type: ChatType,
+    pub contact: bool,
+    pub pinned: bool,
+    pub archived: bool,
+    pub muted: bool,
+    pub unread_count: i32,
+    pub unread_mentions_count: i32,
+    pub marked_unread: bool,
+    pub last_message_date: Option<DateTime<Utc>>,
+    /// Ids of custom folders that contain this dialog
+    pub folders: Vec<i32>,
+}
+
+pub async fn save_dialogs(client: &Client, output_dir: &Path, date: DateTime<Utc>) {
+    let folders = match client
+        .invoke(&tl::functions::messages::GetDialogFilters {})
+        .await
+    {
+        Ok(filters) => filters.into_iter().filter_map(to_folder).collect(),
+        Err(e) => {
+            log::error!("Cannot load chat folders: {}", e);
+            vec![]
+        }
+    };
+
+    let mut dialogs = vec![];
+    let mut dialogs_iter = client.iter_dialogs();
+    loop {
+        match dialogs_iter.next().await {
+            Ok(Some(dialog)) => dialogs.extend(to_record(&dialog, date)),
+            Ok(None) => break,
+            Err(e) => {
+                log::error!("Cannot load dialogs: {}", e);
+                return;
+            }
+        }
+    }
+    for dialog in dialogs.iter_mut() {
+        dialog.folders = folders
+            .iter()
+            .filter(|folder| in_folder(folder, dialog))
+            .map(|folder| folder.id)
+            .collect();
+    }
+
+    let info = DialogsInfo {
+        date,
+        folders,
+        dialogs,
+    };
+    let file = File::create(output_dir.join(FILE_NAME)).unwrap();
+    serde_json::to_writer_pretty(&file, &info).unwrap();
+}
+
+fn to_record(dialog: &Dialog, date: DateTime<Utc>) -> Option<DialogRecord> {
+    let chat = dialog.chat();
+    let raw = match &dialog.raw {
+        tl::enums::Dialog::Dialog(raw) => raw,
+        tl::enums::Dialog::Folder(_) => return None,
+    };
+    let tl::enums::PeerNotifySettings::Settings(notify_settings) = &raw.notify_settings;
+    let muted = notify_settings
+        .mute_until
+        .map_or(false, |mute_until| mute_until as i64 > date.timestamp());
+    Some(DialogRecord {
+        id: chat.id(),
+        name: chat.name().to_string(),
+        chat_type: chat.chat_type(),
+        contact: match chat {
+            Chat::User(user) => user.contact(),
+            _ => false,
+        },
+        pinned: raw.pinned,
+        archived: raw.folder_id == Some(ARCHIVE_FOLDER_ID),
+        muted,
+        unread_count: raw.unread_count,
+        unread_mentions_count: raw.unread_mentions_count,
+        marked_unread: raw.unread_mark,
+        last_message_date: dialog.last_message.as_ref().map(|message| message.date()),
+        folders: vec![],
+    })
+}
+
+fn to_folder(filter: tl::enums::DialogFilter) -> Option<FolderInfo> {
+    match filter {
+        tl::enums::DialogFilter::Filter(filter) => Some(FolderInfo {
+            id: filter.id,
+            title: filter.title,
+            emoticon: filter.emoticon,
+            shared: false,
+            contacts: filter.contacts,
+            non_contacts: filter.non_contacts,
+            groups: filter.groups,
+            broadcasts: filter.broadcasts,
+            bots: filter.bots,
+            exclude_muted: filter.exclude_muted,
+            exclude_read: filter.exclude_read,
+            exclude_archived: filter.exclude_archived,
+            pinned_chats: peer_ids(&filter.pinned_peers),
+            included_chats: peer_ids(&filter.include_peers),
+            excluded_chats: peer_ids(&filter.exclude_peers),
+        }),
+        tl::enums::DialogFilter::Chatlist(filter) => Some(FolderInfo {
+            id: filter.id,
+            title: filter.title,
+            emoticon: filter.emoticon,
+            shared: true,
+            pinned_chats: peer_ids(&filter.pinned_peers),
+            included_chats: peer_ids(&filter.include_peers),
+            ..Default::default()
+        }),
+        // The default folder contains all chats
+        tl::enums::DialogFilter::Default => None,
+    }
+}
+
+fn peer_ids(peers: &Vec<tl::enums::InputPeer>) -> Vec<i64> {
+    peers
+        .iter()
+        .filter_map(|peer| match peer {
+            tl::enums::InputPeer::User(user) => Some(user.user_id),
+            tl::enums::InputPeer::Chat(chat) => Some(chat.chat_id),
+            tl::enums::InputPeer::Channel(channel) => Some(channel.channel_id),
+            tl::enums::InputPeer::UserFromMessage(user) => Some(user.user_id),
+            tl::enums::InputPeer::ChannelFromMessage(channel) => Some(channel.channel_id),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check the folder rules in the same order as Telegram clients do:
+/// excluded chats, then pinned and included chats, then chat types and exclude flags.
+fn in_folder(folder: &FolderInfo, dialog: &DialogRecord) -> bool {
+    if folder.excluded_chats.contains(&dialog.id) {
+        return false;
+    }
+    if folder.pinned_chats.contains(&dialog.id) || folder.included_chats.contains(&dialog.id) {
+        return true;
+    }
+    let type_matches = match dialog.chat_type {
+        ChatType::User => {
+            if dialog.contact {
+                folder.contacts
+            } else {
+                folder.non_contacts
+            }
+        }
+        ChatType::Bot => folder.bots,
+        ChatType::Group | ChatType::Supergroup => folder.groups,
+        ChatType::Channel => folder.broadcasts,
+    };
+    let read = dialog.unread_count == 0 && !dialog.marked_unread;
+    type_matches
+        && !(folder.exclude_muted && dialog.muted)
+        && !(folder.exclude_read && read)
+        && !(folder.exclude_archived && dialog.archived)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dialog(id: i64, chat_type: ChatType) -> DialogRecord {
+        DialogRecord {
+            id,
+            name: String::from("Chat"),
+            chat_type,
+            contact: false,
+            pinned: false,
+            archived: false,
+            muted: false,
+            unread_count: 0,
+            unread_mentions_count: 0,
+            marked_unread: false,
+            last_message_date: None,
+            folders: vec![],
+        }
+    }
+
+    #[test]
+    fn included_and_excluded_chats() {
+        let folder = FolderInfo {
+            groups: true,
+            included_chats: vec![1],
+            excluded_chats: vec![2],
+            ..Default::default()
+        };
+        assert!(in_folder(&folder, &dialog(1, ChatType::Channel)));
+        assert!(!in_folder(&folder, &dialog(2, ChatType::Group)));
+        assert!(in_folder(&folder, &dialog(3, ChatType::Supergroup)));
+        assert!(!in_folder(&folder, &dialog(4, ChatType::User)));
+    }
+
+    #[test]
+    fn exclude_flags() {
+        let folder = FolderInfo {
+            contacts: true,
+            non_contacts: true,
+            exclude_read: true,
+            exclude_archived: true,
+            included_chats: vec![3],
+            ..Default::default()
+        };
+        let mut unread = dialog(1, ChatType::User);
+        unread.unread_count = 5;
+        let mut archived = dialog(2, ChatType::User);
+        archived.marked_unread = true;
+        archived.archived = true;
+        assert!(in_folder(&folder, &unread));
+        assert!(!in_folder(&folder, &archived));
+        // Explicitly included chats ignore the exclude flags
+        assert!(in_folder(&folder, &dialog(3, ChatType::User)));
+    }
+}
@@ -0,0 +1,429 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EntityType {
+    Mention,
+    Hashtag,
+    Cashtag,
+    BotCommand,
+    Url,
+    Email,
+    Phone,
+    BankCard,
+    Bold,
+    Italic,
+    Underline,
+    Strike,
+    Spoiler,
+    Code,
+    Pre,
+    Blockquote,
+    TextUrl,
+    MentionName,
+    CustomEmoji,
+    Unknown,
+}
+
+/// Formatting of the message text. `offset` and `length` are measured in UTF-16 code units.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct EntityInfo {
+    pub entity_type: EntityType,
+    pub offset: i32,
+    pub length: i32,
+    pub url: Option<String>,
+    pub user_id: Option<i64>,
+    pub language: Option<String>,
+    pub document_id: Option<i64>,
+}
+
+impl EntityInfo {
+    fn new(entity_type: EntityType, offset: i32, length: i32) -> EntityInfo {
+        EntityInfo {
+            entity_type,
+            offset,
+            length,
+            url: None,
+            user_id: None,
+            language: None,
+            document_id: None,
+        }
+    }
+}
+
+impl From<&tl::enums::MessageEntity> for EntityInfo {
+    fn from(entity: &tl::enums::MessageEntity) -> Self {
+        use tl::enums::MessageEntity as E;
+        match entity {
+            E::Unknown(it) => EntityInfo::new(EntityType::Unknown, it.offset, it.length),
+            E::Mention(it) => EntityInfo::new(EntityType::Mention, it.offset, it.length),
+            E::Hashtag(it) => EntityInfo::new(EntityType::Hashtag, it.offset, it.length),
+            E::Cashtag(it) => EntityInfo::new(EntityType::Cashtag, it.offset, it.length),
+            E::BotCommand(it) => EntityInfo::new(EntityType::BotCommand, it.offset, it.length),
+            E::Url(it) => EntityInfo::new(EntityType::Url, it.offset, it.length),
+            E::Email(it) => EntityInfo::new(EntityType::Email, it.offset, it.length),
+            E::Phone(it) => EntityInfo::new(EntityType::Phone, it.offset, it.length),
+            E::BankCard(it) => EntityInfo::new(EntityType::BankCard, it.offset, it.length),
+            E::Bold(it) => EntityInfo::new(EntityType::Bold, it.offset, it.length),
+            E::Italic(it) => EntityInfo::new(EntityType::Italic, it.offset, it.length),
+            E::Underline(it) => EntityInfo::new(EntityType::Underline, it.offset, it.length),
+            E::Strike(it) => EntityInfo::new(EntityType::Strike, it.offset, it.length),
+            E::Spoiler(it) => EntityInfo::new(EntityType::Spoiler, it.offset, it.length),
+            E::Code(it) => EntityInfo::new(EntityType::Code, it.offset, it.length),
+            E::Blockquote(it) => EntityInfo::new(EntityType::Blockquote, it.offset, it.length),
+            E::Pre(it) => EntityInfo {
+                language: Some(it.language.to_string()).filter(|x| !x.is_empty()),
+                ..EntityInfo::new(EntityType::Pre, it.offset, it.length)
+            },
+            E::TextUrl(it) => EntityInfo {
+                url: Some(it.url.to_string()),
+                ..EntityInfo::new(EntityType::TextUrl, it.offset, it.length)
+            },
+            E::MentionName(it) => EntityInfo {
+                user_id: Some(it.user_id),
+                ..EntityInfo::new(EntityType::MentionName, it.offset, it.length)
+            },
+            E::InputMessageEntityMentionName(it) => {
+                EntityInfo::new(EntityType::MentionName, it.offset, it.length)
+            }
+            E::CustomEmoji(it) => EntityInfo {
+                document_id: Some(it.document_id),
+                ..EntityInfo::new(EntityType::CustomEmoji, it.offset, it.length)
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Markup {
+    Markdown,
+    Html,
+}
+
+/// Render the message text with its entities to Markdown or HTML.
+pub fn render(text: &str, entities: &[EntityInfo], markup: Markup) -> String {
+    let utf16: Vec<u16> = text.encode_utf16().collect();
+    let len = utf16.len();
+    let slice =
+        |from: usize, to: usize| String::from_utf16_lossy(&utf16[from.min(len)..to.min(len)]);
+
+    // Outer entities are opened first and closed last
+    let mut sorted: Vec<&EntityInfo> = entities.iter().filter(|it| it.length > 0).collect();
+    sorted.sort_by_key(|it| (it.offset, -it.length));
+
+    let mut boundaries: Vec<usize> = sorted
+        .iter()
+        .flat_map(|it| vec![it.offset as usize, (it.offset + it.length) as usize])
+        .filter(|it| *it < len)
+        .collect();
+    boundaries.push(0);
+    boundaries.push(len);
+    boundaries.sort();
+    boundaries.dedup();
+
+    let end = |entity: &EntityInfo| (entity.offset + entity.length) as usize;
+    let entity_text = |entity: &EntityInfo| slice(entity.offset as usize, end(entity));
+
+    let mut res = String::new();
+    let mut opened: Vec<&EntityInfo> = vec![];
+    for (index, position) in boundaries.iter().enumerate() {
+        let position = *position;
+        // Entities that were opened after the ended one are closed and opened again,
+        // so the tags stay balanced if the entities overlap
+        if let Some(first_ended) = opened.iter().position(|it| end(it) <= position) {
+            let mut reopened = vec![];
+            while opened.len() > first_ended {
+                let last = opened.pop().unwrap();
+                push(&mut res, &closing_tag(last, markup), &opened, markup);
+                if end(last) > position {
+                    reopened.push(last);
+                }
+            }
+            for entity in reopened.into_iter().rev() {
+                // Markdown blockquote is not closed, so it's not opened again in the middle of the line
+                if markup == Markup::Html || entity.entity_type != EntityType::Blockquote {
+                    push(
+                        &mut res,
+                        &opening_tag(entity, &entity_text(entity), markup),
+                        &opened,
+                        markup,
+                    );
+                }
+                opened.push(entity);
+            }
+        }
+        for entity in sorted.iter().filter(|it| it.offset as usize == position) {
+            push(
+                &mut res,
+                &opening_tag(entity, &entity_text(entity), markup),
+                &opened,
+                markup,
+            );
+            opened.push(entity);
+        }
+        if let Some(next) = boundaries.get(index + 1) {
+            let raw = opened
+                .iter()
+                .any(|it| it.entity_type == EntityType::Code || it.entity_type == EntityType::Pre);
+            let text = escape(&slice(position, *next), markup, raw);
+            push(&mut res, &text, &opened, markup);
+        }
+    }
+    while let Some(last) = opened.pop() {
+        push(&mut res, &closing_tag(last, markup), &opened, markup);
+    }
+    res
+}
+
+/// Every line of a Markdown blockquote starts with `> `
+fn push(res: &mut String, text: &str, opened: &[&EntityInfo], markup: Markup) {
+    let quoted = markup == Markup::Markdown
+        && opened
+            .iter()
+            .any(|it| it.entity_type == EntityType::Blockquote);
+    if quoted {
+        res.push_str(text.replace('\n', "\n> ").as_str());
+    } else {
+        res.push_str(text);
+    }
+}
+
+fn opening_tag(entity: &EntityInfo, entity_text: &str, markup: Markup) -> String {
+    match markup {
+        Markup::Markdown => match entity.entity_type {
+            EntityType::Bold => String::from("**"),
+            EntityType::Italic => String::from("_"),
+            EntityType::Underline => String::from("<u>"),
+            EntityType::Strike => String::from("~~"),
+            EntityType::Spoiler => String::from("||"),
+            EntityType::Code => String::from("`"),
+            EntityType::Pre => format!("```{}\n", entity.language.as_deref().unwrap_or("")),
+            EntityType::Blockquote => String::from("> "),
+            EntityType::TextUrl | EntityType::MentionName => String::from("["),
+            _ => String::new(),
+        },
+        Markup::Html => match entity.entity_type {
+            EntityType::Bold => String::from("<b>"),
+            EntityType::Italic => String::from("<i>"),
+            EntityType::Underline => String::from("<u>"),
+            EntityType::Strike => String::from("<s>"),
+            EntityType::Spoiler => String::from("<span class=\"spoiler\">"),
+            EntityType::Code => String::from("<code>"),
+            EntityType::Pre => match entity.language.as_deref() {
+                Some(language) => format!(
+                    "<pre><code class=\"language-{}\">",
+                    escape(language, markup, false)
+                ),
+                None => String::from("<pre><code>"),
+            },
+            EntityType::Blockquote => String::from("<blockquote>"),
+            EntityType::TextUrl => format!(
+                "<a href=\"{}\">",
+                escape(entity.url.as_deref().unwrap_or(""), markup, false)
+            ),
+            EntityType::MentionName => {
+                format!("<a href=\"tg://user?id={}\">", entity.user_id.unwrap_or(0))
+            }
+            EntityType::Url => format!("<a href=\"{}\">", escape(entity_text, markup, false)),
+            EntityType::Email => {
+                format!("<a href=\"mailto:{}\">", escape(entity_text, markup, false))
+            }
+            EntityType::Phone => format!("<a href=\"tel:{}\">", escape(entity_text, markup, false)),
+            _ => String::new(),
+        },
+    }
+}
+
+fn closing_tag(entity: &EntityInfo, markup: Markup) -> String {
+    match markup {
+        Markup::Markdown => match entity.entity_type {
+            EntityType::Bold => String::from("**"),
+            EntityType::Italic => String::from("_"),
+            EntityType::Underline => String::from("</u>"),
+            EntityType::Strike => String::from("~~"),
+            EntityType::Spoiler => String::from("||"),
+            EntityType::Code => String::from("`"),
+            EntityType::Pre => String::from("\n```"),
+            EntityType::TextUrl => {
+                format!("]({})", escape_url(entity.url.as_deref().unwrap_or("")))
+            }
+            EntityType::MentionName => format!("](tg://user?id={})", entity.user_id.unwrap_or(0)),
+            _ => String::new(),
+        },
+        Markup::Html => match entity.entity_type {
+            EntityType::Bold => String::from("</b>"),
+            EntityType::Italic => String::from("</i>"),
+            EntityType::Underline => String::from("</u>"),
+            EntityType::Strike => String::from("</s>"),
+            EntityType::Spoiler => String::from("</span>"),
+            EntityType::Code => String::from("</code>"),
+            EntityType::Pre => String::from("</code></pre>"),
+            EntityType::Blockquote => String::from("</blockquote>"),
+            EntityType::TextUrl
+            | EntityType::MentionName
+            | EntityType::Url
+            | EntityType::Email
+            | EntityType::Phone => String::from("</a>"),
+            _ => String::new(),
+        },
+    }
+}
+
+fn escape(text: &str, markup: Markup, raw: bool) -> String {
+    let mut res = String::with_capacity(text.len());
+    for char in text.chars() {
+        match markup {
+            Markup::Html => match char {
+                '&' => res.push_str("&amp;"),
+                '<' => res.push_str("&lt;"),
+                '>' => res.push_str("&gt;"),
+                '"' => res.push_str("&quot;"),
+                _ => res.push(char),
+            },
+            Markup::Markdown => {
+                if !raw && "\\`*_~[]|".contains(char) {
+                    res.push('\\');
+                }
+                res.push(char)
+            }
+        }
+    }
+    res
+}
+
+/// Unbalanced parentheses break the link destination in Markdown
+fn escape_url(url: &str) -> String {
+    url.replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entity(entity_type: EntityType, offset: i32, length: i32) -> EntityInfo {
+        EntityInfo::new(entity_type, offset, length)
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!("Hello", render("Hello", &[], Markup::Markdown));
+        assert_eq!("a &lt; b", render("a < b", &[], Markup::Html));
+    }
+
+    #[test]
+    fn bold_markdown() {
+        let entities = vec![entity(EntityType::Bold, 6, 5)];
+        assert_eq!(
+            "Hello **world**",
+            render("Hello world", &entities, Markup::Markdown)
+        );
+    }
+
+    #[test]
+    fn nested_html() {
+        let entities = vec![
+            entity(EntityType::Italic, 0, 11),
+            entity(EntityType::Bold, 6, 5),
+        ];
+        assert_eq!(
+            "<i>Hello <b>world</b></i>",
+            render("Hello world", &entities, Markup::Html)
+        );
+    }
+
+    #[test]
+    fn text_url() {
+        let entities = vec![EntityInfo {
+            url: Some(String::from("https://example.com")),
+            ..entity(EntityType::TextUrl, 0, 4)
+        }];
+        assert_eq!(
+            "[link](https://example.com)",
+            render("link", &entities, Markup::Markdown)
+        );
+        assert_eq!(
+            "<a href=\"https://example.com\">link</a>",
+            render("link", &entities, Markup::Html)
+        );
+    }
+
+    #[test]
+    fn utf16_offsets() {
+        // The emoji takes two UTF-16 code units
+        let entities = vec![entity(EntityType::Bold, 3, 2)];
+        assert_eq!("😀 **hi**", render("😀 hi", &entities, Markup::Markdown));
+    }
+
+    #[test]
+    fn code_is_not_escaped() {
+        let entities = vec![entity(EntityType::Code, 0, 5)];
+        assert_eq!("`a_b*c`", render("a_b*c", &entities, Markup::Markdown));
+    }
+
+    #[test]
+    fn overlapping_entities() {
+        // Bold "Hello wo", italic "lo world"
+        let entities = vec![
+            entity(EntityType::Bold, 0, 8),
+            entity(EntityType::Italic, 3, 8),
+        ];
+        assert_eq!(
+            "<b>Hel<i>lo wo</i></b><i>rld</i>",
+            render("Hello world", &entities, Markup::Html)
+        );
+        assert_eq!(
+            "**Hel_lo wo_**_rld_",
+            render("Hello world", &entities, Markup::Markdown)
+        );
+    }
+
+    #[test]
+    fn text_url_with_parenthesis() {
+        let entities = vec![EntityInfo {
+            url: Some(String::from(
+                "https://en.wikipedia.org/wiki/Rust_(language)",
+            )),
+            ..entity(EntityType::TextUrl, 0, 4)
+        }];
+        assert_eq!(
+            "[Rust](https://en.wikipedia.org/wiki/Rust_\\(language\\))",
+            render("Rust", &entities, Markup::Markdown)
+        );
+    }
+
+    #[test]
+    fn multiline_blockquote() {
+        let entities = vec![entity(EntityType::Blockquote, 0, 12)];
+        assert_eq!(
+            "> first\n> second",
+            render("first\nsecond", &entities, Markup::Markdown)
+        );
+        let entities = vec![entity(EntityType::Blockquote, 0, 12)];
+        assert_eq!(
+            "> first\n> second\nthird",
+            render("first\nsecond\nthird", &entities, Markup::Markdown)
+        );
+    }
+}
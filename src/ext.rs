@@ -18,11 +18,19 @@
  * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::types::{ContactInfo, DiceInfo, ForwardInfo, GeoInfo, GeoLiveInfo, Member, ReplyInfo};
+use crate::entities::EntityInfo;
+use crate::opts::PhotoSizePolicy;
+use crate::reply_markup::ReplyMarkupInfo;
+use crate::types::{
+    to_date, Attachment, ChatDetails, ChatType, ContactInfo, DiceInfo, ForwardInfo, ForwardOrigin,
+    GameInfo, GeoInfo, GeoLiveInfo, InvoiceInfo, Member, PollInfo, QuoteInfo, ReplyInfo, StoryInfo,
+    VenueInfo, WebPageInfo,
+};
 use async_trait::async_trait;
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
 use grammers_client::client::auth::InvocationError;
-use grammers_client::types::{Chat, Media, Message};
+use grammers_client::types::photo_sizes::PhotoSize;
+use grammers_client::types::{Chat, Media, Message, Photo, User};
 use grammers_client::Client;
 use grammers_mtproto::mtp::RpcError;
 use grammers_tl_types as tl;
@@ -34,8 +42,15 @@ pub trait MessageExt {
     fn geo_live(&self) -> Option<GeoLiveInfo>;
     fn dice(&self) -> Option<DiceInfo>;
     fn contact(&self) -> Option<ContactInfo>;
+    fn poll(&self) -> Option<PollInfo>;
+    fn web_page(&self) -> Option<WebPageInfo>;
+    fn web_page_photo(&self, client: &Client) -> Option<Photo>;
+    fn entities(&self) -> Vec<EntityInfo>;
     fn fwd_from(&self) -> Option<ForwardInfo>;
     fn reply_to(&self) -> Option<ReplyInfo>;
+    fn keyboard(&self) -> Option<ReplyMarkupInfo>;
+    /// Media that has no own method in grammers
+    fn other_media(&self) -> Option<Attachment>;
 }
 
 impl MessageExt for Message {
@@ -87,11 +102,47 @@ impl MessageExt for Message {
             None
         }
     }
+
+    fn poll(&self) -> Option<PollInfo> {
+        let media = self.media()?;
+        if let Media::Poll(poll) = media {
+            Some((&poll).into())
+        } else {
+            None
+        }
+    }
+
+    fn web_page(&self) -> Option<WebPageInfo> {
+        let media = self.media()?;
+        if let Media::WebPage(web_page) = media {
+            Some((&web_page.raw.webpage).into())
+        } else {
+            None
+        }
+    }
+
+    fn web_page_photo(&self, client: &Client) -> Option<Photo> {
+        let media = self.media()?;
+        if let Media::WebPage(web_page) = media {
+            if let tl::enums::WebPage::Page(page) = web_page.raw.webpage {
+                return page
+                    .photo
+                    .map(|photo| Photo::from_raw(photo, client.clone()));
+            }
+        }
+        None
+    }
+
+    fn entities(&self) -> Vec<EntityInfo> {
+        self.fmt_entities()
+            .map(|entities| entities.iter().map(|it| it.into()).collect())
+            .unwrap_or(vec![])
+    }
+
     fn fwd_from(&self) -> Option<ForwardInfo> {
         let tl::enums::MessageFwdHeader::Header(data) = self.forward_header()?;
-        let date =
-            DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(data.date as i64, 0), Utc);
-        let from_id = if let Some(from_id) = data.from_id {
+        let date = to_date(data.date);
+        let from_id = if let Some(from_id) = &data.from_id {
             if let tl::enums::Peer::User(user) = from_id {
                 Some(user.user_id)
             } else {
@@ -100,16 +151,208 @@ impl MessageExt for Message {
         } else {
             None
         };
+        let origin = match &data.from_id {
+            Some(tl::enums::Peer::User(user)) => Some(ForwardOrigin::User { id: user.user_id }),
+            Some(tl::enums::Peer::Chat(chat)) => Some(ForwardOrigin::Chat { id: chat.chat_id }),
+            Some(tl::enums::Peer::Channel(channel)) => Some(ForwardOrigin::Channel {
+                id: channel.channel_id,
+                post_id: data.channel_post,
+                signature: data.post_author.clone(),
+            }),
+            None => data
+                .from_name
+                .as_ref()
+                .map(|name| ForwardOrigin::HiddenUser {
+                    name: name.to_string(),
+                }),
+        };
         Some(ForwardInfo {
             from_id,
             from_name: data.from_name.clone(),
             date,
+            origin,
+            // Users and chats of the response contain the origin even if it's not a member of the chat
+            origin_name: data
+                .from_id
+                .as_ref()
+                .and_then(|peer| self.chats.get(peer))
+                .map(|chat| chat.name().to_string())
+                .filter(|name| !name.is_empty()),
+            saved_from_peer: data.saved_from_peer.as_ref().map(|it| it.into()),
+            saved_from_message_id: data.saved_from_msg_id,
+            psa_type: data.psa_type.clone(),
         })
     }
 
     fn reply_to(&self) -> Option<ReplyInfo> {
-        self.reply_to_message_id()
-            .map(|to_message_id| ReplyInfo { to_message_id })
+        match self.reply_header()? {
+            tl::enums::MessageReplyHeader::Header(header) => {
+                let quote = header.quote_text.as_ref().map(|text| QuoteInfo {
+                    text: text.to_string(),
+                    offset: header.quote_offset,
+                    entities: header
+                        .quote_entities
+                        .as_ref()
+                        .map(|entities| entities.iter().map(|it| it.into()).collect())
+                        .unwrap_or(vec![]),
+                });
+                Some(ReplyInfo {
+                    to_message_id: header.reply_to_msg_id,
+                    to_peer: header.reply_to_peer_id.as_ref().map(|it| it.into()),
+                    top_message_id: header.reply_to_top_id,
+                    forum_topic: header.forum_topic,
+                    to_scheduled: header.reply_to_scheduled,
+                    quote,
+                })
+            }
+            tl::enums::MessageReplyHeader::MessageReplyStoryHeader(_) => None,
+        }
+    }
+
+    fn keyboard(&self) -> Option<ReplyMarkupInfo> {
+        self.reply_markup().map(|markup| (&markup).into())
+    }
+
+    fn other_media(&self) -> Option<Attachment> {
+        let attachment = match self.raw.media.as_ref()? {
+            tl::enums::MessageMedia::Empty => return None,
+            tl::enums::MessageMedia::Venue(venue) => Attachment::Venue(VenueInfo {
+                point: match &venue.geo {
+                    tl::enums::GeoPoint::Point(point) => Some(GeoInfo {
+                        longitude: point.long,
+                        latitude: point.lat,
+                        accuracy_radius: point.accuracy_radius,
+                    }),
+                    tl::enums::GeoPoint::Empty => None,
+                },
+                title: venue.title.clone(),
+                address: venue.address.clone(),
+                provider: venue.provider.clone(),
+                venue_id: venue.venue_id.clone(),
+                venue_type: venue.venue_type.clone(),
+            }),
+            tl::enums::MessageMedia::Game(game) => {
+                let tl::enums::Game::Game(game) = &game.game;
+                Attachment::Game(GameInfo {
+                    id: game.id,
+                    short_name: game.short_name.clone(),
+                    title: game.title.clone(),
+                    description: game.description.clone(),
+                })
+            }
+            tl::enums::MessageMedia::Invoice(invoice) => Attachment::Invoice(InvoiceInfo {
+                title: invoice.title.clone(),
+                description: invoice.description.clone(),
+                currency: invoice.currency.clone(),
+                total_amount: invoice.total_amount,
+                start_param: invoice.start_param.clone(),
+                test: invoice.test,
+                shipping_address_requested: invoice.shipping_address_requested,
+                receipt_message_id: invoice.receipt_msg_id,
+            }),
+            tl::enums::MessageMedia::Story(story) => Attachment::Story(StoryInfo {
+                peer: (&story.peer).into(),
+                id: story.id,
+                via_mention: story.via_mention,
+            }),
+            media => Attachment::Unsupported {
+                type_name: media_type_name(media),
+            },
+        };
+        Some(attachment)
+    }
+}
+
+/// Stable name of the media type
+pub fn media_type_name(media: &tl::enums::MessageMedia) -> String {
+    let name = match media {
+        tl::enums::MessageMedia::Empty => "empty",
+        tl::enums::MessageMedia::Photo(_) => "photo",
+        tl::enums::MessageMedia::Document(_) => "document",
+        tl::enums::MessageMedia::Geo(_) => "geo",
+        tl::enums::MessageMedia::GeoLive(_) => "geo_live",
+        tl::enums::MessageMedia::Contact(_) => "contact",
+        tl::enums::MessageMedia::Venue(_) => "venue",
+        tl::enums::MessageMedia::Game(_) => "game",
+        tl::enums::MessageMedia::Invoice(_) => "invoice",
+        tl::enums::MessageMedia::Poll(_) => "poll",
+        tl::enums::MessageMedia::Dice(_) => "dice",
+        tl::enums::MessageMedia::WebPage(_) => "web_page",
+        tl::enums::MessageMedia::Story(_) => "story",
+        tl::enums::MessageMedia::Unsupported => "unsupported",
+        // Media types that are added in newer layers
+        #[allow(unreachable_patterns)]
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+pub trait PhotoExt {
+    fn upload_date(&self) -> Option<DateTime<Utc>>;
+    /// Sizes of the photo that should be downloaded. The first one is the main one.
+    fn sizes(&self, policy: &PhotoSizePolicy) -> Vec<PhotoSize>;
+}
+
+impl PhotoExt for Photo {
+    fn upload_date(&self) -> Option<DateTime<Utc>> {
+        if let Some(tl::enums::Photo::Photo(photo)) = &self.raw.photo {
+            Some(to_date(photo.date))
+        } else {
+            None
+        }
+    }
+
+    fn sizes(&self, policy: &PhotoSizePolicy) -> Vec<PhotoSize> {
+        // Path sizes are svg outlines of the image, they can't be saved as a jpg file
+        let thumbs: Vec<PhotoSize> = self
+            .thumbs()
+            .into_iter()
+            .filter(|size| !matches!(size, PhotoSize::Empty(_) | PhotoSize::Path(_)))
+            .collect();
+        let largest = thumbs.iter().max_by_key(|size| size.size()).cloned();
+        match policy {
+            PhotoSizePolicy::Largest => largest.into_iter().collect(),
+            PhotoSizePolicy::Smallest => thumbs
+                .iter()
+                .min_by_key(|size| size.size())
+                .cloned()
+                .into_iter()
+                .collect(),
+            PhotoSizePolicy::Type(photo_type) => thumbs
+                .iter()
+                .find(|size| &size.photo_type() == photo_type)
+                .cloned()
+                .or(largest)
+                .into_iter()
+                .collect(),
+            PhotoSizePolicy::All => {
+                let mut res = thumbs;
+                res.sort_by_key(|size| std::cmp::Reverse(size.size()));
+                res
+            }
+        }
+    }
+}
+
+pub trait PhotoSizeExt {
+    fn dimensions(&self) -> Option<(i32, i32)>;
+}
+
+impl PhotoSizeExt for PhotoSize {
+    fn dimensions(&self) -> Option<(i32, i32)> {
+        match self {
+            PhotoSize::Size(size) => Some((size.width, size.height)),
+            PhotoSize::Progressive(size) => Some((size.width, size.height)),
+            PhotoSize::Cached(size) => Some((size.width, size.height)),
+            _ => None,
+        }
+    }
+}
+
+fn invite_link(invite: tl::enums::ExportedChatInvite) -> Option<String> {
+    match invite {
+        tl::enums::ExportedChatInvite::ChatInviteExported(invite) => Some(invite.link),
+        tl::enums::ExportedChatInvite::ChatInvitePublicJoinRequests => None,
     }
 }
 
@@ -117,9 +360,14 @@ impl MessageExt for Message {
 pub trait ChatExt: Send {
     fn id(&self) -> i64;
     fn name(&self) -> String;
+    async fn users(&self, client: &Client) -> Vec<User>;
+    fn to_members(&self, users: &Vec<User>) -> Vec<Member>;
     async fn members(&self, client: &Client) -> Vec<Member>;
     fn visual_id(&self) -> String;
     fn skip_backup(&self) -> bool;
+    fn chat_type(&self) -> ChatType;
+    fn creation_date(&self) -> Option<DateTime<Utc>>;
+    async fn details(&self, client: &Client) -> ChatDetails;
 }
 
 #[async_trait]
@@ -132,19 +380,19 @@ impl ChatExt for Chat {
         self.name().to_string()
     }
 
-    async fn members(&self, client: &Client) -> Vec<Member> {
+    async fn users(&self, client: &Client) -> Vec<User> {
         let mut res = vec![];
         if let Chat::User(user) = self {
-            res.push(Member::Me);
-            res.push(user.into());
+            res.push(user.clone());
+        } else if let Chat::Channel(_) = self {
+            // Participants of broadcast channels are available for admins only
         } else {
             let mut participant_iter = client.iter_participants(self);
             loop {
                 let next = participant_iter.next().await;
                 match next {
                     Ok(Some(next_one)) => {
-                        let member = next_one.user.into();
-                        res.push(member);
+                        res.push(next_one.user);
                     }
                     Ok(None) => break,
                     Err(InvocationError::Rpc(RpcError {
@@ -156,10 +404,9 @@ impl ChatExt for Chat {
                         if name == "FLOOD_WAIT" {
                             log::warn!("Flood wait: {}", value.unwrap());
                             sleep(Duration::from_secs(value.unwrap() as u64))
-                        } else if name == "FILE_MIGRATE" {
-                            log::warn!("File migrate: {}", value.unwrap());
                         } else {
-                            log::error!("Error {}, {:?}", name, value)
+                            log::error!("Cannot load participants. Error {}, {:?}", name, value);
+                            break;
                         }
                     }
                     Err(e) => panic!("{}", e),
@@ -169,6 +416,20 @@ impl ChatExt for Chat {
         res
     }
 
+    fn to_members(&self, users: &Vec<User>) -> Vec<Member> {
+        let mut res = vec![];
+        if let Chat::User(_) = self {
+            res.push(Member::Me);
+        }
+        res.extend(users.iter().map(|user| user.into()));
+        res
+    }
+
+    async fn members(&self, client: &Client) -> Vec<Member> {
+        let users = self.users(client).await;
+        self.to_members(&users)
+    }
+
     fn visual_id(&self) -> String {
         if let Chat::User(user) = &self {
             let username = user.username().unwrap_or("NO_USERNAME");
@@ -185,4 +446,105 @@ impl ChatExt for Chat {
             Chat::Channel(_) => true,
         }
     }
+
+    fn chat_type(&self) -> ChatType {
+        match self {
+            Chat::User(user) => {
+                if user.is_bot() {
+                    ChatType::Bot
+                } else {
+                    ChatType::User
+                }
+            }
+            Chat::Group(group) => {
+                if let tl::enums::Chat::Channel(_) = &group.raw {
+                    ChatType::Supergroup
+                } else {
+                    ChatType::Group
+                }
+            }
+            Chat::Channel(_) => ChatType::Channel,
+        }
+    }
+
+    fn creation_date(&self) -> Option<DateTime<Utc>> {
+        let date = match self {
+            Chat::User(_) => None,
+            Chat::Group(group) => match &group.raw {
+                tl::enums::Chat::Chat(chat) => Some(chat.date),
+                tl::enums::Chat::Channel(channel) => Some(channel.date),
+                _ => None,
+            },
+            Chat::Channel(channel) => Some(channel.raw.date),
+        };
+        date.map(to_date)
+    }
+
+    async fn details(&self, client: &Client) -> ChatDetails {
+        let mut details = ChatDetails {
+            chat_type: Some(self.chat_type()),
+            username: self.username().map(|it| it.to_string()),
+            creation_date: self.creation_date(),
+            ..Default::default()
+        };
+        let mut pinned_message_id = None;
+        let packed = self.pack();
+        if let Chat::User(_) = self {
+            if let Some(id) = packed.try_to_input_user() {
+                let request = tl::functions::users::GetFullUser { id };
+                match client.invoke(&request).await {
+                    Ok(tl::enums::users::UserFull::Full(full)) => {
+                        let tl::enums::UserFull::Full(user) = full.full_user;
+                        details.about = user.about;
+                        details.ttl_period = user.ttl_period;
+                        pinned_message_id = user.pinned_msg_id;
+                    }
+                    Err(e) => log::error!("Cannot load full info of {}: {}", self.id(), e),
+                }
+            }
+        } else {
+            let result = if let Some(channel) = packed.try_to_input_channel() {
+                let request = tl::functions::channels::GetFullChannel { channel };
+                client.invoke(&request).await
+            } else {
+                let request = tl::functions::messages::GetFullChat { chat_id: self.id() };
+                client.invoke(&request).await
+            };
+            match result {
+                Ok(tl::enums::messages::ChatFull::Full(full)) => match full.full_chat {
+                    tl::enums::ChatFull::Full(chat) => {
+                        details.about = Some(chat.about);
+                        details.invite_link = chat.exported_invite.and_then(invite_link);
+                        details.ttl_period = chat.ttl_period;
+                        pinned_message_id = chat.pinned_msg_id;
+                    }
+                    tl::enums::ChatFull::ChannelFull(channel) => {
+                        details.about = Some(channel.about);
+                        details.invite_link = channel.exported_invite.and_then(invite_link);
+                        details.ttl_period = channel.ttl_period;
+                        details.slow_mode_seconds = channel.slowmode_seconds;
+                        pinned_message_id = channel.pinned_msg_id;
+                    }
+                },
+                Err(e) => log::error!("Cannot load full info of {}: {}", self.id(), e),
+            }
+        }
+
+        // Full info contains only the last pinned message, so all of them are searched separately
+        let mut pinned_iter = client
+            .search_messages(self)
+            .filter(tl::enums::MessagesFilter::InputMessagesFilterPinned);
+        loop {
+            match pinned_iter.next().await {
+                Ok(Some(message)) => details.pinned_message_ids.push(message.id()),
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("Cannot load pinned messages of {}: {}", self.id(), e);
+                    details.pinned_message_ids = pinned_message_id.into_iter().collect();
+                    break;
+                }
+            }
+        }
+        details
+    }
 }
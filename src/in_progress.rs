@@ -38,6 +38,11 @@ pub struct InProgressInfo {
 
     pub file_issue: i64,
     pub file_issue_count: i32,
+
+    #[serde(default)]
+    pub continue_from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub continue_until: Option<DateTime<Utc>>,
 }
 
 impl InProgressInfo {
@@ -57,6 +62,9 @@ impl InProgressInfo {
 
             file_issue: chat_ctx.file_issue,
             file_issue_count: chat_ctx.file_issue_count,
+
+            continue_from: chat_ctx.continue_from,
+            continue_until: chat_ctx.continue_until,
         }
     }
 }
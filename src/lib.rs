@@ -21,12 +21,26 @@
 extern crate core;
 
 mod actions;
+mod admin_log;
 mod attachment_type;
+mod avatars;
 pub mod backup;
+mod comments;
 pub mod companion;
+mod contacts;
 mod context;
+mod dialogs;
+pub mod entities;
 mod ext;
 mod in_progress;
+pub mod list_chats;
 mod logs;
+pub mod media_policy;
 pub mod opts;
+mod polls;
+mod reply_markup;
+mod rules;
+mod scheduled;
+pub mod selectors;
 mod types;
+mod users;
@@ -0,0 +1,242 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::ext::ChatExt;
+use crate::types::ChatType;
+use clap::ValueEnum;
+use grammers_client::types::Chat;
+use grammers_client::Client;
+use grammers_mtproto::mtp::RpcError;
+use grammers_mtsender::InvocationError;
+use grammers_tl_types as tl;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[derive(ValueEnum, Debug, Clone)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Serialize)]
+pub struct ChatRecord {
+    pub id: i64,
+    pub chat_type: ChatType,
+    pub title: String,
+    pub visual_id: String,
+    pub participants: Option<i32>,
+    /// Total amount of messages reported by Telegram. It may include deleted messages
+    pub approx_messages: Option<usize>,
+    pub backed_up: bool,
+}
+
+/// Print all dialogs of the account
+pub async fn list_chats(client: &Client, output_dir: &Path, format: ListFormat) {
+    let saved_chats = saved_chat_ids(output_dir);
+    let mut records = vec![];
+    let mut dialogs = client.iter_dialogs();
+    loop {
+        match dialogs.next().await {
+            Ok(Some(dialog)) => {
+                let chat = dialog.chat();
+                records.push(ChatRecord {
+                    id: chat.id(),
+                    chat_type: chat.chat_type(),
+                    title: chat.name().to_string(),
+                    visual_id: chat.visual_id(),
+                    participants: participants(chat),
+                    approx_messages: total_messages(client, chat).await,
+                    backed_up: saved_chats.contains(&chat.id()),
+                });
+            }
+            Ok(None) => break,
+            Err(InvocationError::Rpc(RpcError {
+                name,
+                value: Some(wait_time),
+                ..
+            })) if name == "FLOOD_WAIT" => {
+                log::warn!("Flood wait: {}", wait_time);
+                sleep(Duration::from_secs(wait_time as u64))
+            }
+            Err(e) => {
+                log::error!("Cannot load dialogs: {}", e);
+                return;
+            }
+        }
+    }
+
+    match format {
+        ListFormat::Table => print!("{}", to_table(&records)),
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&records).unwrap()),
+        ListFormat::Csv => print!("{}", to_csv(&records)),
+    }
+}
+
+async fn total_messages(client: &Client, chat: &Chat) -> Option<usize> {
+    loop {
+        match client.iter_messages(chat).total().await {
+            Ok(total) => return Some(total),
+            Err(InvocationError::Rpc(RpcError {
+                name,
+                value: Some(wait_time),
+                ..
+            })) if name == "FLOOD_WAIT" => {
+                log::warn!("Flood wait: {}", wait_time);
+                sleep(Duration::from_secs(wait_time as u64))
+            }
+            Err(e) => {
+                log::error!("Cannot load the amount of messages of {}: {}", chat.id(), e);
+                return None;
+            }
+        }
+    }
+}
+
+fn participants(chat: &Chat) -> Option<i32> {
+    match chat {
+        Chat::User(_) => Some(2),
+        Chat::Group(group) => match &group.raw {
+            tl::enums::Chat::Chat(chat) => Some(chat.participants_count),
+            tl::enums::Chat::Channel(channel) => channel.participants_count,
+            _ => None,
+        },
+        Chat::Channel(channel) => channel.raw.participants_count,
+    }
+}
+
+/// Ids of chats from the `chats` folder. Folders are named `{id}.{visual_id}`
+fn saved_chat_ids(output_dir: &Path) -> Vec<i64> {
+    let entries = match fs::read_dir(output_dir.join("chats")) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.split('.').next().and_then(|id| id.parse::<i64>().ok())
+        })
+        .collect()
+}
+
+const HEADERS: [&'static str; 7] = [
+    "id",
+    "type",
+    "title",
+    "visual_id",
+    "participants",
+    "messages",
+    "backed_up",
+];
+
+fn to_row(record: &ChatRecord) -> [String; 7] {
+    [
+        record.id.to_string(),
+        format!("{:?}", record.chat_type),
+        record.title.clone(),
+        record.visual_id.clone(),
+        record
+            .participants
+            .map_or(String::new(), |it| it.to_string()),
+        record
+            .approx_messages
+            .map_or(String::new(), |it| it.to_string()),
+        if record.backed_up { "yes" } else { "no" }.to_string(),
+    ]
+}
+
+fn to_table(records: &Vec<ChatRecord>) -> String {
+    let rows: Vec<[String; 7]> = records.iter().map(to_row).collect();
+    let mut widths = HEADERS.map(|header| header.chars().count());
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+            .collect();
+        format!("{}\n", line.join("  ").trim_end())
+    };
+    let mut res = format_row(HEADERS.to_vec());
+    for row in &rows {
+        res.push_str(&format_row(row.iter().map(|it| it.as_str()).collect()));
+    }
+    res
+}
+
+fn to_csv(records: &Vec<ChatRecord>) -> String {
+    let mut res = format!("{}\n", HEADERS.join(","));
+    for record in records {
+        let row: Vec<String> = to_row(record).iter().map(|it| escape_csv(it)).collect();
+        res.push_str(&format!("{}\n", row.join(",")));
+    }
+    res
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(title: &str) -> ChatRecord {
+        ChatRecord {
+            id: 42,
+            chat_type: ChatType::Group,
+            title: title.to_string(),
+            visual_id: title.to_string(),
+            participants: Some(3),
+            approx_messages: None,
+            backed_up: true,
+        }
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            "id,type,title,visual_id,participants,messages,backed_up\n\
+             42,Group,\"Hello, \"\"world\"\"\",\"Hello, \"\"world\"\"\",3,,yes\n",
+            to_csv(&vec![record("Hello, \"world\"")])
+        );
+    }
+
+    #[test]
+    fn table() {
+        assert_eq!(
+            "id  type   title  visual_id  participants  messages  backed_up\n\
+             42  Group  Team   Team       3                       yes\n",
+            to_table(&vec![record("Team")])
+        );
+    }
+}
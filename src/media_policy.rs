@@ -0,0 +1,291 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::types::Attachment;
+use grammers_client::types::Document;
+use grammers_tl_types as tl;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const MB: i64 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum MediaType {
+    Photo,
+    Video,
+    Round,
+    Voice,
+    Audio,
+    /// GIFs. They are sent as silent mp4 videos
+    Animation,
+    /// Stickers, including animated and video stickers
+    Sticker,
+    File,
+}
+
+impl MediaType {
+    pub fn of_document(doc: &Document) -> MediaType {
+        if doc.is_round_message() {
+            return MediaType::Round;
+        }
+        if doc.is_voice_message() {
+            return MediaType::Voice;
+        }
+        if let Some(tl::enums::Document::Document(document)) = &doc.raw.document {
+            let attributes = &document.attributes;
+            // Animated stickers also have the `Animated` attribute
+            if attributes
+                .iter()
+                .any(|it| matches!(it, tl::enums::DocumentAttribute::Sticker(_)))
+            {
+                return MediaType::Sticker;
+            }
+            if attributes
+                .iter()
+                .any(|it| matches!(it, tl::enums::DocumentAttribute::Animated))
+            {
+                return MediaType::Animation;
+            }
+        }
+        match doc.mime_type() {
+            Some(mime_type) if mime_type.starts_with("video/") => MediaType::Video,
+            Some(mime_type) if mime_type.starts_with("audio/") => MediaType::Audio,
+            _ => MediaType::File,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MediaRule {
+    Always,
+    Never,
+    /// Maximum size in bytes
+    Limit(i64),
+}
+
+/// Rule for a media type in the `type=rule` format, e.g. `video=never`, `voice=20`, `photo=always`.
+/// The number is the maximum size in MB.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MediaTypeRule {
+    pub media_type: MediaType,
+    pub rule: MediaRule,
+}
+
+impl FromStr for MediaTypeRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (media_type, rule) = s.split_once('=').ok_or(format!(
+            "Media rule should have the `type=rule` format: `{}`",
+            s
+        ))?;
+        let media_type = match media_type {
+            "photo" => MediaType::Photo,
+            "video" => MediaType::Video,
+            "round" => MediaType::Round,
+            "voice" => MediaType::Voice,
+            "audio" => MediaType::Audio,
+            "animation" => MediaType::Animation,
+            "sticker" => MediaType::Sticker,
+            "file" => MediaType::File,
+            _ => return Err(format!("Unknown media type `{}`", media_type)),
+        };
+        let rule = match rule {
+            "always" => MediaRule::Always,
+            "never" => MediaRule::Never,
+            limit => {
+                let megabytes = limit
+                    .parse::<i64>()
+                    .map_err(|_| format!("Unknown media rule `{}`", limit))?;
+                if megabytes < 0 {
+                    return Err(format!("Size limit should not be negative: `{}`", limit));
+                }
+                let bytes = megabytes
+                    .checked_mul(MB)
+                    .ok_or(format!("Size limit is too large: `{}`", limit))?;
+                MediaRule::Limit(bytes)
+            }
+        };
+        Ok(MediaTypeRule { media_type, rule })
+    }
+}
+
+impl<'de> Deserialize<'de> for MediaTypeRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rule = String::deserialize(deserializer)?;
+        MediaTypeRule::from_str(rule.as_str()).map_err(Error::custom)
+    }
+}
+
+/// Reason why the media was not downloaded
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SkipReason {
+    Disabled,
+    TooLarge,
+}
+
+impl SkipReason {
+    pub fn to_attachment(
+        self,
+        media_type: MediaType,
+        size: i64,
+        thumbnail: Option<String>,
+    ) -> Attachment {
+        match self {
+            SkipReason::Disabled => Attachment::Skipped {
+                media_type,
+                size,
+                thumbnail,
+            },
+            SkipReason::TooLarge => Attachment::TooLarge { size, thumbnail },
+        }
+    }
+}
+
+/// Decides which media is downloaded. Types without a rule use the global size limit.
+#[derive(Clone, Debug, Default)]
+pub struct MediaPolicy {
+    rules: HashMap<MediaType, MediaRule>,
+    default_limit: Option<i64>,
+}
+
+impl MediaPolicy {
+    pub fn new(default_limit: Option<i64>, rules: &[MediaTypeRule]) -> MediaPolicy {
+        MediaPolicy {
+            rules: rules
+                .iter()
+                .map(|rule| (rule.media_type, rule.rule))
+                .collect(),
+            default_limit,
+        }
+    }
+
+    /// Policy that doesn't download any media
+    pub fn nothing() -> MediaPolicy {
+        let rules = [
+            MediaType::Photo,
+            MediaType::Video,
+            MediaType::Round,
+            MediaType::Voice,
+            MediaType::Audio,
+            MediaType::Animation,
+            MediaType::Sticker,
+            MediaType::File,
+        ]
+        .into_iter()
+        .map(|media_type| (media_type, MediaRule::Never))
+        .collect();
+        MediaPolicy {
+            rules,
+            default_limit: None,
+        }
+    }
+
+    /// Copy of the policy with the given rules on top of the existing ones
+    pub fn overridden(&self, default_limit: Option<i64>, rules: &[MediaTypeRule]) -> MediaPolicy {
+        let mut policy = self.clone();
+        if default_limit.is_some() {
+            policy.default_limit = default_limit;
+        }
+        for rule in rules {
+            policy.rules.insert(rule.media_type, rule.rule);
+        }
+        policy
+    }
+
+    pub fn check(&self, media_type: MediaType, size: i64) -> Result<(), SkipReason> {
+        let rule = self.rules.get(&media_type).cloned().unwrap_or(
+            self.default_limit
+                .map_or(MediaRule::Always, MediaRule::Limit),
+        );
+        match rule {
+            MediaRule::Always => Ok(()),
+            MediaRule::Never => Err(SkipReason::Disabled),
+            MediaRule::Limit(limit) if size > limit => Err(SkipReason::TooLarge),
+            MediaRule::Limit(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(s: &str) -> MediaTypeRule {
+        MediaTypeRule::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            MediaTypeRule {
+                media_type: MediaType::Voice,
+                rule: MediaRule::Limit(20 * MB)
+            },
+            rule("voice=20")
+        );
+        assert_eq!(MediaRule::Never, rule("video=never").rule);
+        assert_eq!(MediaRule::Always, rule("photo=always").rule);
+        assert!(MediaTypeRule::from_str("video").is_err());
+        assert!(MediaTypeRule::from_str("gif=never").is_err());
+        assert!(MediaTypeRule::from_str("video=big").is_err());
+        assert!(MediaTypeRule::from_str("video=-1").is_err());
+        assert!(MediaTypeRule::from_str("video=9223372036854775807").is_err());
+        assert_eq!(MediaType::Sticker, rule("sticker=never").media_type);
+    }
+
+    #[test]
+    fn check() {
+        let policy = MediaPolicy::new(
+            Some(10 * MB),
+            &[rule("photo=always"), rule("voice=20"), rule("video=never")],
+        );
+        assert_eq!(Ok(()), policy.check(MediaType::Photo, 50 * MB));
+        assert_eq!(Ok(()), policy.check(MediaType::Voice, 15 * MB));
+        assert_eq!(
+            Err(SkipReason::TooLarge),
+            policy.check(MediaType::Voice, 25 * MB)
+        );
+        assert_eq!(Err(SkipReason::Disabled), policy.check(MediaType::Video, 1));
+        assert_eq!(
+            Err(SkipReason::TooLarge),
+            policy.check(MediaType::File, 11 * MB)
+        );
+        assert_eq!(
+            Ok(()),
+            MediaPolicy::default().check(MediaType::File, 11 * MB)
+        );
+    }
+
+    #[test]
+    fn overridden() {
+        let policy = MediaPolicy::new(Some(10 * MB), &[rule("video=never")])
+            .overridden(Some(100 * MB), &[rule("photo=never")]);
+        assert_eq!(Ok(()), policy.check(MediaType::File, 50 * MB));
+        assert_eq!(Err(SkipReason::Disabled), policy.check(MediaType::Video, 1));
+        assert_eq!(Err(SkipReason::Disabled), policy.check(MediaType::Photo, 1));
+        assert_eq!(
+            Err(SkipReason::Disabled),
+            MediaPolicy::nothing().check(MediaType::Voice, 1)
+        );
+    }
+}
@@ -18,8 +18,14 @@
  * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::list_chats::ListFormat;
+use crate::media_policy::MediaTypeRule;
+use crate::selectors::ChatSelector;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use clap::Parser;
 use clap::ValueHint;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 //#[clap(after_help = "Beware `-d`, dragons be here")]
 // We can put something at the end
@@ -33,15 +39,17 @@ pub struct Opts {
 
     /// List of chats that are going to be saved. All chats are saved by default.
     ///
-    /// If both included-chats and excluded_chats have the same value, the chat will be excluded.
+    /// A chat is selected by id, `name:/regex/`, `username:name` or `type:user|bot|group|supergroup|channel`.
+    /// If a chat matches both included-chats and excluded-chats, the chat will be excluded.
     #[clap(value_parser, short, long)]
-    pub included_chats: Vec<i64>,
+    pub included_chats: Vec<ChatSelector>,
 
     /// List of chats that are going to be excluded from saving.
     ///
-    /// If both included-chats and excluded_chats have the same value, the chat will be excluded.
+    /// Uses the same selectors as included-chats.
+    /// If a chat matches both included-chats and excluded-chats, the chat will be excluded.
     #[clap(value_parser, short, long)]
-    pub excluded_chats: Vec<i64>,
+    pub excluded_chats: Vec<ChatSelector>,
 
     /// Size of batches with messages.
     #[clap(value_parser, long, default_value = "1000")]
@@ -70,15 +78,89 @@ pub struct Opts {
     pub panic_to_stderr: bool,
 
     /// Maximum size of the attachment in MB.
+    ///
+    /// Applied to media types that have no rule in --media-policy.
     #[clap(value_parser, long)]
     pub file_limit: Option<i32>,
 
+    /// Download rules for media types in the `type=rule` format.
+    ///
+    /// Types: photo, video, round, voice, audio, animation, sticker, file.
+    /// GIFs are animations and are not affected by the video rule.
+    /// Rules: `always`, `never` or the maximum size in MB.
+    /// E.g. `--media-policy photo=always voice=20 video=never file=100`
+    #[clap(value_parser, long)]
+    pub media_policy: Vec<MediaTypeRule>,
+
     /// Skip saving chat if there is more participants. -1 for no limits
     #[clap(value_parser, long, default_value = "20")]
     pub max_participants: i32,
 
+    /// Download preview photos of web pages
+    #[clap(value_parser, long)]
+    pub save_web_page_previews: bool,
+
+    /// Download profile photos of me, chat members and group chats into the `avatars` folder
+    #[clap(value_parser, long)]
+    pub save_avatars: bool,
+
+    /// Download all profile photos instead of the current one. Works with --save-avatars
+    #[clap(value_parser, long)]
+    pub avatar_history: bool,
+
+    /// Save bio, phone, bot info and common chats of me and chat members.
+    ///
+    /// Last seen statuses of chat members are saved into `members/statuses.json`
+    #[clap(value_parser, long)]
+    pub user_details: bool,
+
+    /// Save comments to posts of channels from the linked discussion group.
+    ///
+    /// Channels are saved only if they are selected by included-chats.
+    #[clap(value_parser, long)]
+    pub save_comments: bool,
+
+    /// Maximum amount of comments saved for a single post
+    #[clap(value_parser, long)]
+    pub comments_limit: Option<usize>,
+
+    /// Save the admin log of supergroups and channels where we have admin rights.
+    ///
+    /// Telegram keeps the admin log for 48 hours only, so the backup should run more often to save all events.
+    #[clap(value_parser, long)]
+    pub save_admin_log: bool,
+
+    /// Size of photos that is downloaded: `largest`, `smallest`, `all` or a size type letter (e.g. `m`, `x`, `y`).
+    ///
+    /// If the photo has no size of the requested type, the largest one is downloaded.
+    #[clap(value_parser, long, default_value = "largest")]
+    pub photo_size: PhotoSizePolicy,
+
+    /// Download thumbnails of videos and documents into the `media/thumbnails` folder
+    #[clap(value_parser, long)]
+    pub save_thumbnails: bool,
+
+    /// Save messages since this date only: `2021-07-24`, `2021-07-24T10:00:00Z` or relative as `90d`, `2w`, `12h`.
+    ///
+    /// Older messages are loaded on the next run without this option.
+    #[clap(value_parser = parse_date, long)]
+    pub since: Option<DateTime<Utc>>,
+
+    /// Save messages until this date only. Uses the same format as --since.
+    ///
+    /// Newer messages are loaded on the next run without this option.
+    #[clap(value_parser = parse_date, long)]
+    pub until: Option<DateTime<Utc>>,
+
+    /// JSON file with per-chat rules. The first rule that matches a chat overrides
+    /// media policy, since, until, batch size and max participants of this chat.
+    ///
+    /// E.g. `[{"chats": ["type:bot"], "since": "2023-01-01"}, {"chats": ["name:/^Work/"], "text_only": true}]`
+    #[clap(value_parser, long, value_hint = ValueHint::FilePath)]
+    pub rules_file: Option<PathBuf>,
+
     #[clap(subcommand)]
-    pub auth: Option<SubCommand>,
+    pub subcommand: Option<SubCommand>,
 
     /// Run test mode
     #[clap(value_parser, long, hide = true)]
@@ -89,6 +171,8 @@ pub struct Opts {
 pub enum SubCommand {
     /// Start authentication process
     Auth(Auth),
+    /// Print all chats with their ids, types and sizes
+    ListChats(ListChats),
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -101,3 +185,153 @@ pub struct Auth {
     #[clap(value_parser, long, default_value = "tg_backup.session")]
     pub session_file_name: String,
 }
+
+#[derive(Parser, Debug, Clone)]
+pub struct ListChats {
+    /// Output format
+    #[clap(value_enum, long, default_value = "table")]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PhotoSizePolicy {
+    Largest,
+    Smallest,
+    All,
+    Type(String),
+}
+
+impl FromStr for PhotoSizePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "largest" => Ok(PhotoSizePolicy::Largest),
+            "smallest" => Ok(PhotoSizePolicy::Smallest),
+            "all" => Ok(PhotoSizePolicy::All),
+            letter if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_alphabetic()) => {
+                Ok(PhotoSizePolicy::Type(letter.to_string()))
+            }
+            _ => Err(format!(
+                "Unknown photo size `{}`. Use `largest`, `smallest`, `all` or a size type letter",
+                s
+            )),
+        }
+    }
+}
+
+/// Relative dates are limited to 1000 years
+const MAX_RELATIVE_HOURS: i64 = 1000 * 366 * 24;
+
+pub(crate) fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
+    parse_date_at(s, Utc::now())
+}
+
+fn parse_date_at(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc));
+    }
+    let (amount, unit) = s.split_at(s.len() - s.chars().last().map_or(0, |c| c.len_utf8()));
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("Incorrect date `{}`", s))?;
+    if amount <= 0 {
+        return Err(format!("Relative date should be positive `{}`", s));
+    }
+    let hours_in_unit = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 24 * 7,
+        _ => return Err(format!("Unknown unit of the relative date `{}`", s)),
+    };
+    let hours = amount
+        .checked_mul(hours_in_unit)
+        .filter(|hours| *hours <= MAX_RELATIVE_HOURS)
+        .ok_or(format!("Relative date is too large `{}`", s))?;
+    now.checked_sub_signed(Duration::hours(hours))
+        .ok_or(format!("Relative date is too large `{}`", s))
+}
+
+/// Check that `since` is before `until`. Missing `until` means now.
+pub(crate) fn check_date_range(
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<(), String> {
+    match since {
+        Some(since) if since >= until.unwrap_or(Utc::now()) => Err(format!(
+            "Date `since` ({}) should be before `until` ({})",
+            since,
+            until.map_or(String::from("now"), |until| until.to_string())
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::opts::{check_date_range, parse_date_at, PhotoSizePolicy};
+    use chrono::{TimeZone, Utc};
+    use std::str::FromStr;
+
+    #[test]
+    fn photo_size_policy() {
+        assert_eq!(
+            Ok(PhotoSizePolicy::Largest),
+            PhotoSizePolicy::from_str("largest")
+        );
+        assert_eq!(
+            Ok(PhotoSizePolicy::Smallest),
+            PhotoSizePolicy::from_str("smallest")
+        );
+        assert_eq!(Ok(PhotoSizePolicy::All), PhotoSizePolicy::from_str("all"));
+        assert_eq!(
+            Ok(PhotoSizePolicy::Type(String::from("x"))),
+            PhotoSizePolicy::from_str("x")
+        );
+        assert!(PhotoSizePolicy::from_str("xy").is_err());
+        assert!(PhotoSizePolicy::from_str("1").is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let now = Utc.ymd(2021, 7, 24).and_hms(12, 0, 0);
+        assert_eq!(
+            Ok(Utc.ymd(2021, 1, 31).and_hms(0, 0, 0)),
+            parse_date_at("2021-01-31", now)
+        );
+        assert_eq!(
+            Ok(Utc.ymd(2021, 1, 31).and_hms(10, 0, 0)),
+            parse_date_at("2021-01-31T12:00:00+02:00", now)
+        );
+        assert_eq!(
+            Ok(Utc.ymd(2021, 4, 25).and_hms(12, 0, 0)),
+            parse_date_at("90d", now)
+        );
+        assert_eq!(
+            Ok(Utc.ymd(2021, 7, 24).and_hms(0, 0, 0)),
+            parse_date_at("12h", now)
+        );
+        assert!(parse_date_at("90", now).is_err());
+        assert!(parse_date_at("d", now).is_err());
+        assert!(parse_date_at("", now).is_err());
+        assert!(parse_date_at("-5d", now).is_err());
+        assert!(parse_date_at("0d", now).is_err());
+        assert!(parse_date_at("99999999999999d", now).is_err());
+        assert!(parse_date_at("9223372036854775807w", now).is_err());
+    }
+
+    #[test]
+    fn date_range() {
+        let date = Utc.ymd(2021, 7, 24).and_hms(12, 0, 0);
+        let later = Utc.ymd(2021, 8, 24).and_hms(12, 0, 0);
+        assert!(check_date_range(Some(date), Some(later)).is_ok());
+        assert!(check_date_range(Some(date), None).is_ok());
+        assert!(check_date_range(None, Some(date)).is_ok());
+        assert!(check_date_range(Some(later), Some(date)).is_err());
+        assert!(check_date_range(Some(date), Some(date)).is_err());
+        assert!(check_date_range(Some(Utc::now() + chrono::Duration::days(1)), None).is_err());
+    }
+}
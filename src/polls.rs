@@ -0,0 +1,153 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::ext::MessageExt;
+use crate::types::{Attachment, MessageInfo, PollInfo};
+use grammers_client::types::Chat;
+use grammers_client::Client;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &'static str = "open_polls.json";
+
+/// Maximum amount of message ids in a single request
+const CHUNK_SIZE: usize = 100;
+
+/// Ids of messages with polls that were not closed at the moment of saving.
+/// Results of these polls are refreshed on the next run.
+pub struct OpenPolls {
+    path: PathBuf,
+}
+
+impl OpenPolls {
+    pub fn create(path: &Path) -> OpenPolls {
+        OpenPolls {
+            path: path.join(FILE_NAME),
+        }
+    }
+
+    pub fn read_data(&self) -> Vec<i32> {
+        File::open(&self.path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or(vec![])
+    }
+
+    pub fn write_data(&self, data: &Vec<i32>) {
+        if data.is_empty() {
+            let _ = fs::remove_file(&self.path);
+            return;
+        }
+        let file = File::create(&self.path).unwrap();
+        serde_json::to_writer_pretty(&file, data).unwrap();
+    }
+
+    /// Add polls to the list without refreshing them. Used if the loading of the chat was interrupted.
+    pub fn add(&self, new_open_polls: &Vec<i32>) {
+        let mut open_polls = self.read_data();
+        for id in new_open_polls {
+            if !open_polls.contains(id) {
+                open_polls.push(*id);
+            }
+        }
+        self.write_data(&open_polls);
+    }
+
+    /// Reload polls that were open during previous runs and update them in the saved messages.
+    /// Polls that are still open stay in the list together with `new_open_polls`.
+    pub async fn refresh(
+        &self,
+        client: &Client,
+        chat: &Chat,
+        chat_path: &Path,
+        new_open_polls: &Vec<i32>,
+    ) {
+        let previous = self.read_data();
+        let mut still_open: Vec<i32> = vec![];
+        let mut updated: HashMap<i32, PollInfo> = HashMap::new();
+
+        for chunk in previous.chunks(CHUNK_SIZE) {
+            match client.get_messages_by_id(chat, chunk).await {
+                Ok(messages) => {
+                    for (id, message) in chunk.iter().zip(messages) {
+                        let poll = message.and_then(|it| it.poll());
+                        if let Some(poll) = poll {
+                            if !poll.closed {
+                                still_open.push(*id);
+                            }
+                            updated.insert(*id, poll);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::error!("Cannot refresh open polls: {}", e);
+                    still_open.extend_from_slice(chunk);
+                }
+            }
+        }
+
+        if !updated.is_empty() {
+            update_saved_polls(chat_path, updated);
+        }
+
+        for id in new_open_polls {
+            if !still_open.contains(id) {
+                still_open.push(*id);
+            }
+        }
+        self.write_data(&still_open);
+    }
+}
+
+fn update_saved_polls(chat_path: &Path, mut polls: HashMap<i32, PollInfo>) {
+    let messages_path = chat_path.join("messages");
+    let entries = match fs::read_dir(messages_path) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|it| it.ok()) {
+        if polls.is_empty() {
+            break;
+        }
+        let file = BufReader::new(File::open(entry.path()).unwrap());
+        let parsed: Result<Vec<MessageInfo>, _> = serde_json::from_reader(file);
+        let mut messages = match parsed {
+            Ok(messages) => messages,
+            Err(err) => {
+                log::error!("Cannot parse data from {:?}, error {}", entry.path(), err);
+                continue;
+            }
+        };
+        let mut changed = false;
+        for message in messages.iter_mut() {
+            if let Some(poll) = polls.remove(&message.id) {
+                message.attachment = Some(Attachment::Poll(poll));
+                changed = true;
+            }
+        }
+        if changed {
+            let file = File::create(entry.path()).unwrap();
+            serde_json::to_writer_pretty(&file, &messages).unwrap();
+        }
+    }
+}
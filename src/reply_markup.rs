@@ -0,0 +1,249 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+
+/// Keyboard that is attached to the message by a bot
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum ReplyMarkupInfo {
+    /// Buttons under the message
+    Inline {
+        rows: Vec<Vec<ButtonInfo>>,
+    },
+    /// Buttons that replace the keyboard of the user
+    Keyboard {
+        rows: Vec<Vec<ButtonInfo>>,
+        resize: bool,
+        single_use: bool,
+        selective: bool,
+        placeholder: Option<String>,
+    },
+    HideKeyboard {
+        selective: bool,
+    },
+    ForceReply {
+        single_use: bool,
+        selective: bool,
+        placeholder: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ButtonInfo {
+    pub text: String,
+    pub button_type: ButtonType,
+    pub url: Option<String>,
+    /// Query of the switch inline button, or the user id of the user profile button
+    pub data: Option<String>,
+    /// Raw callback data of the callback button. It's not necessarily a valid UTF-8 string
+    #[serde(default)]
+    pub callback_data: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum ButtonType {
+    Text,
+    Url,
+    Callback,
+    RequestPhone,
+    RequestGeoLocation,
+    RequestPoll,
+    SwitchInline,
+    Game,
+    Buy,
+    UrlAuth,
+    UserProfile,
+    Unsupported,
+}
+
+impl From<&tl::enums::ReplyMarkup> for ReplyMarkupInfo {
+    fn from(markup: &tl::enums::ReplyMarkup) -> Self {
+        match markup {
+            tl::enums::ReplyMarkup::ReplyInlineMarkup(markup) => ReplyMarkupInfo::Inline {
+                rows: to_rows(&markup.rows),
+            },
+            tl::enums::ReplyMarkup::ReplyKeyboardMarkup(markup) => ReplyMarkupInfo::Keyboard {
+                rows: to_rows(&markup.rows),
+                resize: markup.resize,
+                single_use: markup.single_use,
+                selective: markup.selective,
+                placeholder: markup.placeholder.clone(),
+            },
+            tl::enums::ReplyMarkup::ReplyKeyboardHide(markup) => ReplyMarkupInfo::HideKeyboard {
+                selective: markup.selective,
+            },
+            tl::enums::ReplyMarkup::ReplyKeyboardForceReply(markup) => {
+                ReplyMarkupInfo::ForceReply {
+                    single_use: markup.single_use,
+                    selective: markup.selective,
+                    placeholder: markup.placeholder.clone(),
+                }
+            }
+        }
+    }
+}
+
+fn to_rows(rows: &Vec<tl::enums::KeyboardButtonRow>) -> Vec<Vec<ButtonInfo>> {
+    rows.iter()
+        .map(|tl::enums::KeyboardButtonRow::Row(row)| {
+            row.buttons.iter().map(|it| it.into()).collect()
+        })
+        .collect()
+}
+
+impl From<&tl::enums::KeyboardButton> for ButtonInfo {
+    fn from(button: &tl::enums::KeyboardButton) -> Self {
+        let (text, button_type, url, data) = match button {
+            tl::enums::KeyboardButton::Button(button) => {
+                (button.text.clone(), ButtonType::Text, None, None)
+            }
+            tl::enums::KeyboardButton::Url(button) => (
+                button.text.clone(),
+                ButtonType::Url,
+                Some(button.url.clone()),
+                None,
+            ),
+            tl::enums::KeyboardButton::Callback(button) => {
+                (button.text.clone(), ButtonType::Callback, None, None)
+            }
+            tl::enums::KeyboardButton::RequestPhone(button) => {
+                (button.text.clone(), ButtonType::RequestPhone, None, None)
+            }
+            tl::enums::KeyboardButton::RequestGeoLocation(button) => (
+                button.text.clone(),
+                ButtonType::RequestGeoLocation,
+                None,
+                None,
+            ),
+            tl::enums::KeyboardButton::RequestPoll(button) => {
+                (button.text.clone(), ButtonType::RequestPoll, None, None)
+            }
+            tl::enums::KeyboardButton::SwitchInline(button) => (
+                button.text.clone(),
+                ButtonType::SwitchInline,
+                None,
+                Some(button.query.clone()),
+            ),
+            tl::enums::KeyboardButton::Game(button) => {
+                (button.text.clone(), ButtonType::Game, None, None)
+            }
+            tl::enums::KeyboardButton::Buy(button) => {
+                (button.text.clone(), ButtonType::Buy, None, None)
+            }
+            tl::enums::KeyboardButton::UrlAuth(button) => (
+                button.text.clone(),
+                ButtonType::UrlAuth,
+                Some(button.url.clone()),
+                None,
+            ),
+            tl::enums::KeyboardButton::UserProfile(button) => (
+                button.text.clone(),
+                ButtonType::UserProfile,
+                None,
+                Some(button.user_id.to_string()),
+            ),
+            tl::enums::KeyboardButton::WebView(button) => (
+                button.text.clone(),
+                ButtonType::Unsupported,
+                Some(button.url.clone()),
+                None,
+            ),
+            tl::enums::KeyboardButton::SimpleWebView(button) => (
+                button.text.clone(),
+                ButtonType::Unsupported,
+                Some(button.url.clone()),
+                None,
+            ),
+            // Input buttons are used to send messages only
+            tl::enums::KeyboardButton::InputKeyboardButtonUrlAuth(button) => {
+                (button.text.clone(), ButtonType::Unsupported, None, None)
+            }
+            tl::enums::KeyboardButton::InputKeyboardButtonUserProfile(button) => {
+                (button.text.clone(), ButtonType::Unsupported, None, None)
+            }
+            // Buttons that are added in newer layers
+            #[allow(unreachable_patterns)]
+            _ => (String::new(), ButtonType::Unsupported, None, None),
+        };
+        let callback_data = match button {
+            tl::enums::KeyboardButton::Callback(button) => Some(button.data.clone()),
+            _ => None,
+        };
+        ButtonInfo {
+            text,
+            button_type,
+            url,
+            data,
+            callback_data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn inline_keyboard() {
+        let markup = tl::enums::ReplyMarkup::ReplyInlineMarkup(tl::types::ReplyInlineMarkup {
+            rows: vec![tl::enums::KeyboardButtonRow::Row(
+                tl::types::KeyboardButtonRow {
+                    buttons: vec![
+                        tl::enums::KeyboardButton::Callback(tl::types::KeyboardButtonCallback {
+                            requires_password: false,
+                            text: String::from("Approve"),
+                            data: vec![0, 159, 146, 150],
+                        }),
+                        tl::enums::KeyboardButton::Url(tl::types::KeyboardButtonUrl {
+                            text: String::from("Open"),
+                            url: String::from("https://example.com"),
+                        }),
+                    ],
+                },
+            )],
+        });
+        let info: ReplyMarkupInfo = (&markup).into();
+        assert_eq!(
+            json!({
+                "type": "Inline",
+                "rows": [[
+                    {
+                        "text": "Approve",
+                        "button_type": "Callback",
+                        "url": null,
+                        "data": null,
+                        "callback_data": [0, 159, 146, 150]
+                    },
+                    {
+                        "text": "Open",
+                        "button_type": "Url",
+                        "url": "https://example.com",
+                        "data": null,
+                        "callback_data": null
+                    }
+                ]]
+            }),
+            serde_json::to_value(&info).unwrap()
+        );
+    }
+}
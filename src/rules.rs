@@ -0,0 +1,229 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::context::MainContext;
+use crate::media_policy::{MediaPolicy, MediaTypeRule};
+use crate::opts::{check_date_range, parse_date};
+use crate::selectors::ChatSelector;
+use chrono::{DateTime, Utc};
+use grammers_client::types::Chat;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Rule of the rules file. Settings that are not set fall back to the command line options.
+///
+/// ```json
+/// [
+///   { "chats": ["name:/^Family$/"], "media_policy": ["video=always", "file=always"] },
+///   { "chats": ["name:/^Work/"], "text_only": true, "batch_size": 5000 },
+///   { "chats": ["type:bot"], "since": "2023-01-01" }
+/// ]
+/// ```
+#[derive(Deserialize, Debug)]
+pub struct ChatRule {
+    pub chats: Vec<ChatSelector>,
+    /// Do not download any media
+    #[serde(default)]
+    pub text_only: bool,
+    /// Maximum size of the attachment in MB
+    #[serde(default)]
+    pub file_limit: Option<i32>,
+    #[serde(default)]
+    pub media_policy: Vec<MediaTypeRule>,
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub batch_size: Option<i32>,
+    #[serde(default)]
+    pub max_participants: Option<i32>,
+}
+
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(date) => parse_date(date.as_str()).map(Some).map_err(Error::custom),
+        None => Ok(None),
+    }
+}
+
+pub fn load_rules(path: &Path) -> Result<Vec<ChatRule>, String> {
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    let rules: Vec<ChatRule> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format!("Cannot parse {}: {}", path.display(), e))?;
+    for rule in &rules {
+        check_date_range(rule.since, rule.until)
+            .map_err(|e| format!("Incorrect rule in {}: {}", path.display(), e))?;
+    }
+    Ok(rules)
+}
+
+/// Settings of a single chat after applying the first matching rule
+pub struct ChatSettings {
+    pub media_policy: MediaPolicy,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub batch_size: i32,
+    pub max_participants: i32,
+}
+
+impl ChatSettings {
+    pub fn of(chat: &Chat, main_ctx: &MainContext) -> ChatSettings {
+        let defaults = ChatSettings {
+            media_policy: main_ctx.media_policy.clone(),
+            since: main_ctx.since,
+            until: main_ctx.until,
+            batch_size: main_ctx.batch_size,
+            max_participants: main_ctx.max_participants,
+        };
+        let rule = find_rule(&main_ctx.rules, |selector| selector.matches(chat));
+        defaults.apply(rule)
+    }
+
+    /// Override the settings from the command line with the settings of the rule
+    fn apply(self, rule: Option<&ChatRule>) -> ChatSettings {
+        let rule = match rule {
+            Some(rule) => rule,
+            None => return self,
+        };
+        let media_policy = if rule.text_only {
+            MediaPolicy::nothing()
+        } else {
+            self.media_policy.overridden(
+                rule.file_limit.map(|x| x as i64 * 1024 * 1024),
+                &rule.media_policy,
+            )
+        };
+        ChatSettings {
+            media_policy,
+            since: rule.since.or(self.since),
+            until: rule.until.or(self.until),
+            batch_size: rule.batch_size.unwrap_or(self.batch_size),
+            max_participants: rule.max_participants.unwrap_or(self.max_participants),
+        }
+    }
+}
+
+/// The first rule that has a matching selector
+fn find_rule<F>(rules: &[ChatRule], matches: F) -> Option<&ChatRule>
+where
+    F: Fn(&ChatSelector) -> bool,
+{
+    rules
+        .iter()
+        .find(|rule| rule.chats.iter().any(|selector| matches(selector)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::media_policy::{MediaType, SkipReason};
+    use crate::types::ChatType;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse() {
+        let rules: Vec<ChatRule> = serde_json::from_str(
+            r#"[
+                { "chats": ["name:/^Family$/", 42], "media_policy": ["video=always"] },
+                { "chats": ["type:group"], "text_only": true, "batch_size": 5000 },
+                { "chats": ["type:bot"], "since": "2023-01-01", "max_participants": 10 }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(3, rules.len());
+        assert_eq!(2, rules[0].chats.len());
+        assert_eq!(1, rules[0].media_policy.len());
+        assert!(rules[1].text_only);
+        assert_eq!(Some(5000), rules[1].batch_size);
+        assert_eq!(Some(Utc.ymd(2023, 1, 1).and_hms(0, 0, 0)), rules[2].since);
+        assert_eq!(None, rules[2].until);
+        assert_eq!(Some(10), rules[2].max_participants);
+
+        assert!(serde_json::from_str::<Vec<ChatRule>>(r#"[{ "chats": ["type:room"] }]"#).is_err());
+        assert!(
+            serde_json::from_str::<Vec<ChatRule>>(r#"[{ "chats": [], "since": "later" }]"#)
+                .is_err()
+        );
+    }
+
+    fn settings() -> ChatSettings {
+        ChatSettings {
+            media_policy: MediaPolicy::default(),
+            since: None,
+            until: None,
+            batch_size: 1000,
+            max_participants: 50,
+        }
+    }
+
+    fn settings_of(rules: &[ChatRule], name: &str, chat_type: ChatType) -> ChatSettings {
+        let rule = find_rule(rules, |selector| {
+            selector.matches_info(1, name, None, chat_type)
+        });
+        settings().apply(rule)
+    }
+
+    #[test]
+    fn apply() {
+        let rules: Vec<ChatRule> = serde_json::from_str(
+            r#"[
+                { "chats": ["name:/^Family$/"], "media_policy": ["video=never"], "since": null },
+                { "chats": ["type:group"], "text_only": true, "batch_size": 5000 },
+                { "chats": ["type:bot"], "since": "2023-01-01", "max_participants": 10 }
+            ]"#,
+        )
+        .unwrap();
+
+        // The first matching rule wins
+        let family = settings_of(&rules, "Family", ChatType::Group);
+        assert_eq!(1000, family.batch_size);
+        assert_eq!(None, family.since);
+        assert_eq!(
+            Err(SkipReason::Disabled),
+            family.media_policy.check(MediaType::Video, 1)
+        );
+        assert_eq!(Ok(()), family.media_policy.check(MediaType::Photo, 1));
+
+        let work = settings_of(&rules, "Work", ChatType::Group);
+        assert_eq!(5000, work.batch_size);
+        assert_eq!(50, work.max_participants);
+        assert_eq!(
+            Err(SkipReason::Disabled),
+            work.media_policy.check(MediaType::Photo, 1)
+        );
+
+        let bot = settings_of(&rules, "Bot", ChatType::Bot);
+        assert_eq!(Some(Utc.ymd(2023, 1, 1).and_hms(0, 0, 0)), bot.since);
+        assert_eq!(10, bot.max_participants);
+
+        // Chats without a matching rule use the command line settings
+        let other = settings_of(&rules, "Alice", ChatType::User);
+        assert_eq!(1000, other.batch_size);
+        assert_eq!(50, other.max_participants);
+        assert_eq!(None, other.since);
+        assert_eq!(Ok(()), other.media_policy.check(MediaType::Video, 1));
+    }
+}
@@ -0,0 +1,105 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::backup::save_attachment;
+use crate::context::{ChatContext, MainContext};
+use crate::ext::MessageExt;
+use crate::types::{msg_to_info, Attachment, MessageInfo};
+use chrono::{DateTime, Utc};
+use grammers_client::types::{Chat, ChatMap, Message};
+use grammers_client::Client;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+const FILE_NAME: &'static str = "scheduled.json";
+
+/// Messages that are scheduled, but not yet sent. The file is rewritten on every run.
+/// The date of a message is its planned send date.
+#[derive(Serialize, Deserialize)]
+pub struct ScheduledInfo {
+    pub date: DateTime<Utc>,
+    pub messages: Vec<MessageInfo>,
+}
+
+pub async fn save_scheduled(
+    client: &Client,
+    chat: &Chat,
+    chat_path: &Path,
+    chat_ctx: &mut ChatContext,
+    main_ctx: &Arc<MainContext>,
+) {
+    let path = chat_path.join(FILE_NAME);
+    let request = tl::functions::messages::GetScheduledHistory {
+        peer: chat.pack().to_input_peer(),
+        hash: 0,
+    };
+    let (messages, users, chats) = match client.invoke(&request).await {
+        Ok(tl::enums::messages::Messages::Messages(data)) => {
+            (data.messages, data.users, data.chats)
+        }
+        Ok(tl::enums::messages::Messages::Slice(data)) => (data.messages, data.users, data.chats),
+        Ok(tl::enums::messages::Messages::ChannelMessages(data)) => {
+            (data.messages, data.users, data.chats)
+        }
+        Ok(tl::enums::messages::Messages::NotModified(_)) => return,
+        Err(e) => {
+            log::error!("Cannot load scheduled messages of {}: {}", chat.id(), e);
+            return;
+        }
+    };
+
+    let chat_map = ChatMap::new(users, chats);
+    // Retry state of the history download should not be affected by scheduled messages
+    let file_issue = chat_ctx.file_issue;
+    let file_issue_count = chat_ctx.file_issue_count;
+    let mut saved_messages = vec![];
+    for raw in messages {
+        let message = match Message::from_raw(client, raw, &chat_map) {
+            Some(message) => message,
+            None => continue,
+        };
+        let attachment = match save_attachment(client, &message, chat_ctx, main_ctx).await {
+            Ok(attachment) => attachment,
+            Err(_) => Some(Attachment::Error(format!(
+                "Cannot load the attachment of the scheduled message {}",
+                message.id()
+            ))),
+        };
+        saved_messages.push(msg_to_info(&message, attachment, None, message.fwd_from()));
+    }
+    chat_ctx.file_issue = file_issue;
+    chat_ctx.file_issue_count = file_issue_count;
+
+    if saved_messages.is_empty() {
+        let _ = fs::remove_file(path);
+        return;
+    }
+
+    let info = ScheduledInfo {
+        date: main_ctx.date,
+        messages: saved_messages,
+    };
+    let file = File::create(path).unwrap();
+    serde_json::to_writer_pretty(&file, &info).unwrap();
+}
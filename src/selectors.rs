@@ -0,0 +1,231 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::ext::ChatExt;
+use crate::types::ChatType;
+use grammers_client::types::Chat;
+use regex::Regex;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Selects chats for the backup. Supported formats:
+/// - `123456` - id of the chat
+/// - `name:/^Team .*/` - regex for the name of the chat
+/// - `username:alice` - username of the chat, with or without `@`
+/// - `type:user`, `type:bot`, `type:group`, `type:supergroup`, `type:channel` - type of the chat.
+///   `type:group` selects supergroups as well
+#[derive(Debug, Clone)]
+pub enum ChatSelector {
+    Id(i64),
+    Name(Regex),
+    Username(String),
+    Type(ChatType),
+}
+
+impl ChatSelector {
+    pub fn matches(&self, chat: &Chat) -> bool {
+        self.matches_info(chat.id(), chat.name(), chat.username(), chat.chat_type())
+    }
+
+    pub(crate) fn matches_info(
+        &self,
+        id: i64,
+        name: &str,
+        username: Option<&str>,
+        chat_type: ChatType,
+    ) -> bool {
+        match self {
+            ChatSelector::Id(selector_id) => *selector_id == id,
+            ChatSelector::Name(regex) => regex.is_match(name),
+            ChatSelector::Username(selector_username) => username.map_or(false, |username| {
+                username.eq_ignore_ascii_case(selector_username)
+            }),
+            ChatSelector::Type(ChatType::Group) => {
+                chat_type == ChatType::Group || chat_type == ChatType::Supergroup
+            }
+            ChatSelector::Type(selector_type) => *selector_type == chat_type,
+        }
+    }
+}
+
+/// Check if the chat should be saved. Excluded chats have priority over included ones.
+/// All chats are included if there are no included selectors.
+pub fn is_selected(included: &[ChatSelector], excluded: &[ChatSelector], chat: &Chat) -> bool {
+    select(included, excluded, |selector| selector.matches(chat))
+}
+
+fn select<F>(included: &[ChatSelector], excluded: &[ChatSelector], matches: F) -> bool
+where
+    F: Fn(&ChatSelector) -> bool,
+{
+    if excluded.iter().any(&matches) {
+        return false;
+    }
+    included.is_empty() || included.iter().any(&matches)
+}
+
+impl FromStr for ChatSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse::<i64>() {
+            return Ok(ChatSelector::Id(id));
+        }
+        if let Some(pattern) = s.strip_prefix("name:") {
+            let pattern = pattern
+                .strip_prefix('/')
+                .and_then(|it| it.strip_suffix('/'))
+                .ok_or(format!(
+                    "Name pattern should be surrounded by slashes: `{}`",
+                    s
+                ))?;
+            return Regex::new(pattern)
+                .map(ChatSelector::Name)
+                .map_err(|e| format!("Incorrect name pattern `{}`: {}", pattern, e));
+        }
+        if let Some(username) = s.strip_prefix("username:") {
+            let username = username.trim_start_matches('@');
+            if username.is_empty() {
+                return Err(format!("Username is empty: `{}`", s));
+            }
+            return Ok(ChatSelector::Username(username.to_string()));
+        }
+        if let Some(chat_type) = s.strip_prefix("type:") {
+            let chat_type = match chat_type {
+                "user" => ChatType::User,
+                "bot" => ChatType::Bot,
+                "group" => ChatType::Group,
+                "supergroup" => ChatType::Supergroup,
+                "channel" => ChatType::Channel,
+                _ => return Err(format!("Unknown chat type `{}`", chat_type)),
+            };
+            return Ok(ChatSelector::Type(chat_type));
+        }
+        Err(format!(
+            "Unknown chat selector `{}`. Use an id, `name:/regex/`, `username:name` or `type:type`",
+            s
+        ))
+    }
+}
+
+impl Display for ChatSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChatSelector::Id(id) => write!(f, "{}", id),
+            ChatSelector::Name(regex) => write!(f, "name:/{}/", regex.as_str()),
+            ChatSelector::Username(username) => write!(f, "username:{}", username),
+            ChatSelector::Type(chat_type) => {
+                let chat_type = match chat_type {
+                    ChatType::User => "user",
+                    ChatType::Bot => "bot",
+                    ChatType::Group => "group",
+                    ChatType::Supergroup => "supergroup",
+                    ChatType::Channel => "channel",
+                };
+                write!(f, "type:{}", chat_type)
+            }
+        }
+    }
+}
+
+impl Serialize for ChatSelector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ChatSelector::Id(id) => serializer.serialize_i64(*id),
+            other => serializer.serialize_str(other.to_string().as_str()),
+        }
+    }
+}
+
+/// Older backups contain numeric ids only
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSelector {
+    Id(i64),
+    Text(String),
+}
+
+impl<'de> Deserialize<'de> for ChatSelector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match RawSelector::deserialize(deserializer)? {
+            RawSelector::Id(id) => Ok(ChatSelector::Id(id)),
+            RawSelector::Text(text) => ChatSelector::from_str(text.as_str()).map_err(Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn selector(s: &str) -> ChatSelector {
+        ChatSelector::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert!(matches!(selector("-100"), ChatSelector::Id(-100)));
+        assert!(matches!(
+            selector("type:bot"),
+            ChatSelector::Type(ChatType::Bot)
+        ));
+        assert!(
+            matches!(selector("username:@alice"), ChatSelector::Username(name) if name == "alice")
+        );
+        assert_eq!("name:/^Team .*/", selector("name:/^Team .*/").to_string());
+        assert!(ChatSelector::from_str("name:Team").is_err());
+        assert!(ChatSelector::from_str("type:room").is_err());
+        assert!(ChatSelector::from_str("alice").is_err());
+    }
+
+    #[test]
+    fn matches() {
+        assert!(selector("name:/^Team .*/").matches_info(1, "Team A", None, ChatType::Group));
+        assert!(!selector("name:/^Team .*/").matches_info(1, "My Team", None, ChatType::Group));
+        assert!(selector("username:Alice").matches_info(1, "A", Some("alice"), ChatType::User));
+        assert!(selector("type:group").matches_info(1, "A", None, ChatType::Supergroup));
+        assert!(!selector("type:supergroup").matches_info(1, "A", None, ChatType::Group));
+    }
+
+    #[test]
+    fn excluded_have_priority() {
+        let included = vec![selector("type:group"), selector("1")];
+        let excluded = vec![selector("name:/Spam/")];
+        let check = |id, name| {
+            select(&included, &excluded, |it| {
+                it.matches_info(id, name, None, ChatType::Group)
+            })
+        };
+        assert!(check(1, "Team"));
+        assert!(!check(1, "Spam"));
+        assert!(select(&[], &[], |_| false));
+    }
+
+    #[test]
+    fn serialization() {
+        let selectors = vec![selector("1"), selector("type:user")];
+        let serialized = serde_json::to_string(&selectors).unwrap();
+        assert_eq!(r#"[1,"type:user"]"#, serialized);
+        let deserialized: Vec<ChatSelector> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(serialized, serde_json::to_string(&deserialized).unwrap());
+    }
+}
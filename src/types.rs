@@ -20,26 +20,101 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use crate::actions::Action;
-use crate::ext::MessageExt;
-use grammers_client::types::media::GeoPoint;
-use grammers_client::types::{Chat, Message, User};
+use crate::entities::EntityInfo;
+use crate::ext::{MessageExt, PhotoExt, PhotoSizeExt};
+use crate::media_policy::MediaType;
+use crate::reply_markup::ReplyMarkupInfo;
+use crate::selectors::ChatSelector;
+use crate::users::UserDetails;
+use chrono::NaiveDateTime;
+use grammers_client::types::media::{GeoPoint, Poll};
+use grammers_client::types::photo_sizes::PhotoSize;
+use grammers_client::types::{Chat, Document, Message, Photo, User};
+use grammers_tl_types as tl;
 
 #[derive(Serialize, Deserialize)]
 pub struct ChatInfo {
     pub name: String,
     pub id: i64,
     pub loaded_up_to: DateTime<Utc>,
+    /// Date of the oldest saved messages if the history is saved since some date only
+    #[serde(default)]
+    pub loaded_since: Option<DateTime<Utc>>,
     pub total_messages: usize,
+    /// Paths of the saved chat photos, relative to the backup directory
+    #[serde(default)]
+    pub avatars: Vec<String>,
+    #[serde(flatten)]
+    pub details: ChatDetails,
+    /// All known names of the chat. The last one is the current name
+    #[serde(default)]
+    pub name_history: Vec<NameRecord>,
+}
+
+impl ChatInfo {
+    pub fn set_name_history(&mut self, mut history: Vec<NameRecord>, date: DateTime<Utc>) {
+        if history.last().map(|it| &it.name) != Some(&self.name) {
+            history.push(NameRecord {
+                name: self.name.clone(),
+                date,
+            });
+        }
+        self.name_history = history;
+    }
+
+    /// Name history of the saved info. Older backups don't have the history, so the saved name is used
+    pub fn previous_name_history(&self) -> Vec<NameRecord> {
+        if self.name_history.is_empty() {
+            vec![NameRecord {
+                name: self.name.clone(),
+                date: self.loaded_up_to,
+            }]
+        } else {
+            self.name_history.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NameRecord {
+    pub name: String,
+    /// The date when the name was seen for the first time
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChatType {
+    User,
+    Bot,
+    Group,
+    Supergroup,
+    Channel,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ChatDetails {
+    pub chat_type: Option<ChatType>,
+    pub username: Option<String>,
+    pub about: Option<String>,
+    pub invite_link: Option<String>,
+    pub creation_date: Option<DateTime<Utc>>,
+    pub pinned_message_ids: Vec<i32>,
+    pub slow_mode_seconds: Option<i32>,
+    pub ttl_period: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct MessageInfo {
     text: String,
-    id: i32,
+    #[serde(default)]
+    entities: Vec<EntityInfo>,
+    pub id: i32,
     pub date: DateTime<Utc>,
-    attachment: Option<Attachment>,
+    pub attachment: Option<Attachment>,
     edit_date: Option<DateTime<Utc>>,
     mentioned: bool,
     outgoing: bool,
@@ -49,12 +124,121 @@ pub struct MessageInfo {
     forwarded_from: Option<ForwardInfo>,
     reply_to: Option<ReplyInfo>,
     action: Option<Action>,
+    /// Path to the comments of the channel post
+    pub comments: Option<String>,
+    /// Messages of the same album have the same grouped id
+    grouped_id: Option<i64>,
+    /// Buttons of the message sent by a bot
+    reply_markup: Option<ReplyMarkupInfo>,
+    /// Bot that was used to send this message as an inline result
+    pub via_bot: Option<ViaBotInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ViaBotInfo {
+    pub id: i64,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AlbumInfo {
+    pub grouped_id: i64,
+    pub message_ids: Vec<i32>,
+    /// Caption of the album. Only one message of the album has a text
+    pub caption: Option<String>,
+}
+
+/// Collect albums of the messages. Albums are ordered by the date of their first message
+pub fn album_index(messages: &Vec<MessageInfo>) -> Vec<AlbumInfo> {
+    let mut albums: Vec<AlbumInfo> = vec![];
+    for message in messages {
+        let grouped_id = match message.grouped_id {
+            Some(grouped_id) => grouped_id,
+            None => continue,
+        };
+        let index = match albums.iter().position(|it| it.grouped_id == grouped_id) {
+            Some(index) => index,
+            None => {
+                albums.push(AlbumInfo {
+                    grouped_id,
+                    message_ids: vec![],
+                    caption: None,
+                });
+                albums.len() - 1
+            }
+        };
+        let album = &mut albums[index];
+        album.message_ids.push(message.id);
+        if album.caption.is_none() && !message.text.is_empty() {
+            album.caption = Some(message.text.clone());
+        }
+    }
+    albums
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct FileInfo {
     pub id: i64,
+    /// Path of the saved file. On disk the file is named `{id}@{original_name}`
     pub path: String,
+    pub original_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub size: Option<i64>,
+    pub upload_date: Option<DateTime<Utc>>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration: Option<i32>,
+    /// Path of the saved thumbnail of a video or a document
+    pub thumbnail: Option<String>,
+    /// Paths of other saved sizes of the photo, from the largest to the smallest
+    #[serde(default)]
+    pub sizes: Vec<String>,
+}
+
+impl FileInfo {
+    pub fn from_photo(id: i64, path: String, photo: &Photo, size: &PhotoSize) -> FileInfo {
+        let (width, height) = size
+            .dimensions()
+            .map_or((None, None), |(width, height)| (Some(width), Some(height)));
+        FileInfo {
+            id,
+            path,
+            original_name: None,
+            mime_type: Some(String::from("image/jpeg")),
+            size: Some(size.size() as i64),
+            upload_date: photo.upload_date(),
+            width,
+            height,
+            duration: None,
+            thumbnail: None,
+            sizes: vec![],
+        }
+    }
+
+    pub fn from_document(path: String, doc: &Document) -> FileInfo {
+        let original_name = doc.name();
+        let mime_type = doc.mime_type();
+        let (width, height) = doc
+            .resolution()
+            .map_or((None, None), |(width, height)| (Some(width), Some(height)));
+        FileInfo {
+            id: doc.id(),
+            path,
+            original_name: if original_name.is_empty() {
+                None
+            } else {
+                Some(original_name.to_string())
+            },
+            mime_type: mime_type.map(|x| x.to_string()),
+            size: Some(doc.size() as i64),
+            upload_date: doc.creation_date(),
+            width,
+            height,
+            duration: doc.duration(),
+            thumbnail: None,
+            sizes: vec![],
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -67,8 +251,28 @@ pub enum Attachment {
     GeoLive(GeoLiveInfo),
     Dice(DiceInfo),
     Contact(ContactInfo),
+    Poll(PollInfo),
+    WebPage(WebPageInfo),
+    Venue(VenueInfo),
+    Game(GameInfo),
+    Invoice(InvoiceInfo),
+    Story(StoryInfo),
+    /// Media that is not supported yet. The type name is the name of the media in the Telegram API
+    Unsupported {
+        type_name: String,
+    },
     PhotoExpired,
-    TooLarge { size: i32 },
+    TooLarge {
+        size: i64,
+        /// Thumbnail of the document that was saved instead of the file
+        thumbnail: Option<String>,
+    },
+    /// Media that was not downloaded because its type is disabled by the media policy
+    Skipped {
+        media_type: MediaType,
+        size: i64,
+        thumbnail: Option<String>,
+    },
     Error(String),
 }
 
@@ -84,9 +288,11 @@ pub fn msg_to_info(
     data: &Message,
     attachment: Option<Attachment>,
     action: Option<Action>,
+    forwarded_from: Option<ForwardInfo>,
 ) -> MessageInfo {
     MessageInfo {
         text: data.text().to_string(),
+        entities: data.entities(),
         id: data.id(),
         date: data.date(),
         attachment,
@@ -96,18 +302,31 @@ pub fn msg_to_info(
         pinned: data.pinned(),
         sender_id: data.sender().map(|x| x.id()),
         sender_name: data.sender().map(|x| x.name().to_string()),
-        forwarded_from: data.fwd_from(),
+        forwarded_from,
         reply_to: data.reply_to(),
         action,
+        comments: None,
+        grouped_id: data.grouped_id(),
+        reply_markup: data.keyboard(),
+        via_bot: data.via_bot_id().map(|id| ViaBotInfo { id, name: None }),
     }
 }
 
-pub fn chat_to_info(data: &Chat, loaded_up_to: DateTime<Utc>, total_messages: usize) -> ChatInfo {
+pub fn chat_to_info(
+    data: &Chat,
+    loaded_up_to: DateTime<Utc>,
+    total_messages: usize,
+    avatars: Vec<String>,
+) -> ChatInfo {
     ChatInfo {
         name: data.name().to_string(),
         id: data.id(),
         loaded_up_to,
+        loaded_since: None,
         total_messages,
+        avatars,
+        details: ChatDetails::default(),
+        name_history: vec![],
     }
 }
 
@@ -115,27 +334,32 @@ pub fn chat_to_info(data: &Chat, loaded_up_to: DateTime<Utc>, total_messages: us
 pub struct BackUpInfo {
     pub date: DateTime<Utc>,
     pub batch_size: i32,
-    pub included_chats: Option<Vec<i64>>,
-    pub excluded_chats: Vec<i64>,
+    pub included_chats: Option<Vec<ChatSelector>>,
+    pub excluded_chats: Vec<ChatSelector>,
+    /// Ids of chats that were matched by the selectors. Filled in at the end of the backup
+    #[serde(default)]
+    pub selected_chats: Vec<i64>,
 }
 
 impl BackUpInfo {
     pub fn init(
         date: DateTime<Utc>,
-        loading_chats: Option<Vec<i64>>,
-        excluded_chats: Vec<i64>,
+        loading_chats: Option<Vec<ChatSelector>>,
+        excluded_chats: Vec<ChatSelector>,
         batch_size: i32,
+        selected_chats: Vec<i64>,
     ) -> BackUpInfo {
         BackUpInfo {
             date,
             batch_size,
             included_chats: loading_chats,
             excluded_chats,
+            selected_chats,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(tag = "type")]
 pub enum Member {
     Me,
@@ -148,6 +372,12 @@ pub enum Member {
         contact: bool,
         mutual_contact: bool,
         deleted: bool,
+        /// Paths of the saved profile photos, relative to the backup directory
+        #[serde(default)]
+        avatars: Vec<String>,
+        /// Extended information, saved with `--user-details`
+        #[serde(default)]
+        details: Option<UserDetails>,
     },
     IdOnly {
         id: i64,
@@ -165,6 +395,26 @@ impl From<User> for Member {
             contact: user.contact(),
             mutual_contact: user.mutual_contact(),
             deleted: user.deleted(),
+            avatars: vec![],
+            details: None,
+        }
+    }
+}
+
+impl Member {
+    pub fn set_avatars(&mut self, user_id: i64, paths: Vec<String>) {
+        if let Member::User { id, avatars, .. } = self {
+            if *id == user_id {
+                *avatars = paths;
+            }
+        }
+    }
+
+    pub fn set_details(&mut self, user_id: i64, user_details: UserDetails) {
+        if let Member::User { id, details, .. } = self {
+            if *id == user_id {
+                *details = Some(user_details);
+            }
         }
     }
 }
@@ -181,6 +431,8 @@ impl From<&User> for Member {
             contact: user.contact(),
             mutual_contact: user.mutual_contact(),
             deleted: user.deleted(),
+            avatars: vec![],
+            details: None,
         }
     }
 }
@@ -190,11 +442,114 @@ pub struct ForwardInfo {
     pub from_id: Option<i64>,
     pub from_name: Option<String>,
     pub date: DateTime<Utc>,
+    pub origin: Option<ForwardOrigin>,
+    /// Display name of the origin, if it's known
+    pub origin_name: Option<String>,
+    pub saved_from_peer: Option<PeerInfo>,
+    pub saved_from_message_id: Option<i32>,
+    pub psa_type: Option<String>,
+}
+
+impl ForwardInfo {
+    /// Resolve the origin name by the known names if the response had no info about the origin
+    pub fn resolve_name(&mut self, names: &HashMap<PeerInfo, String>) {
+        if self.origin_name.is_some() {
+            return;
+        }
+        self.origin_name = match &self.origin {
+            Some(ForwardOrigin::User { id }) => names.get(&PeerInfo::User { id: *id }).cloned(),
+            Some(ForwardOrigin::Chat { id }) => names.get(&PeerInfo::Chat { id: *id }).cloned(),
+            Some(ForwardOrigin::Channel { id, .. }) => {
+                names.get(&PeerInfo::Channel { id: *id }).cloned()
+            }
+            Some(ForwardOrigin::HiddenUser { name }) => Some(name.to_string()),
+            None => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ForwardOrigin {
+    User {
+        id: i64,
+    },
+    Chat {
+        id: i64,
+    },
+    Channel {
+        id: i64,
+        post_id: Option<i32>,
+        signature: Option<String>,
+    },
+    /// The original sender has hidden the link to their account
+    HiddenUser {
+        name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+#[serde(tag = "type")]
+pub enum PeerInfo {
+    User { id: i64 },
+    Chat { id: i64 },
+    Channel { id: i64 },
+}
+
+impl PeerInfo {
+    pub fn id(&self) -> i64 {
+        match self {
+            PeerInfo::User { id } | PeerInfo::Chat { id } | PeerInfo::Channel { id } => *id,
+        }
+    }
+}
+
+impl From<&Chat> for PeerInfo {
+    fn from(chat: &Chat) -> Self {
+        match chat {
+            Chat::User(user) => PeerInfo::User { id: user.id() },
+            Chat::Group(group) => match &group.raw {
+                tl::enums::Chat::Channel(_) | tl::enums::Chat::ChannelForbidden(_) => {
+                    PeerInfo::Channel { id: group.id() }
+                }
+                _ => PeerInfo::Chat { id: group.id() },
+            },
+            Chat::Channel(channel) => PeerInfo::Channel { id: channel.id() },
+        }
+    }
+}
+
+impl From<&tl::enums::Peer> for PeerInfo {
+    fn from(peer: &tl::enums::Peer) -> Self {
+        match peer {
+            tl::enums::Peer::User(user) => PeerInfo::User { id: user.user_id },
+            tl::enums::Peer::Chat(chat) => PeerInfo::Chat { id: chat.chat_id },
+            tl::enums::Peer::Channel(channel) => PeerInfo::Channel {
+                id: channel.channel_id,
+            },
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct ReplyInfo {
-    pub to_message_id: i32,
+    pub to_message_id: Option<i32>,
+    /// Chat of the replied message if it's not the current chat
+    pub to_peer: Option<PeerInfo>,
+    /// Top message of the thread
+    pub top_message_id: Option<i32>,
+    #[serde(default)]
+    pub forum_topic: bool,
+    #[serde(default)]
+    pub to_scheduled: bool,
+    pub quote: Option<QuoteInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QuoteInfo {
+    pub text: String,
+    pub offset: Option<i32>,
+    pub entities: Vec<EntityInfo>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -235,3 +590,244 @@ pub struct ContactInfo {
     pub last_name: String,
     pub vcard: String,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct VenueInfo {
+    pub point: Option<GeoInfo>,
+    pub title: String,
+    pub address: String,
+    pub provider: String,
+    pub venue_id: String,
+    pub venue_type: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GameInfo {
+    pub id: i64,
+    pub short_name: String,
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InvoiceInfo {
+    pub title: String,
+    pub description: String,
+    pub currency: String,
+    /// Price in the smallest units of the currency
+    pub total_amount: i64,
+    pub start_param: String,
+    pub test: bool,
+    pub shipping_address_requested: bool,
+    /// Id of the receipt message if the invoice is paid
+    pub receipt_message_id: Option<i32>,
+}
+
+/// Reference to a story that was shared in the message. The story itself is not saved
+#[derive(Serialize, Deserialize)]
+pub struct StoryInfo {
+    pub peer: PeerInfo,
+    pub id: i32,
+    pub via_mention: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PollInfo {
+    pub id: i64,
+    pub question: String,
+    pub answers: Vec<PollAnswerInfo>,
+    pub closed: bool,
+    pub public_voters: bool,
+    pub multiple_choice: bool,
+    pub quiz: bool,
+    pub close_date: Option<DateTime<Utc>>,
+    pub total_voters: Option<i32>,
+    pub solution: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PollAnswerInfo {
+    pub text: String,
+    pub option: Vec<u8>,
+    pub voters: Option<i32>,
+    /// This answer was chosen by us
+    pub chosen: bool,
+    /// This answer is the correct answer of the quiz
+    pub correct: bool,
+}
+
+impl From<&Poll> for PollInfo {
+    fn from(poll: &Poll) -> Self {
+        let results = poll.raw_results.as_ref();
+        let voters: Vec<&tl::types::PollAnswerVoters> = results
+            .and_then(|it| it.results.as_ref())
+            .map(|it| {
+                it.iter()
+                    .map(|tl::enums::PollAnswerVoters::Voters(voters)| voters)
+                    .collect()
+            })
+            .unwrap_or(vec![]);
+        let answers = poll
+            .raw
+            .answers
+            .iter()
+            .map(|tl::enums::PollAnswer::Answer(answer)| {
+                let answer_voters = voters.iter().find(|it| it.option == answer.option);
+                PollAnswerInfo {
+                    text: answer.text.to_string(),
+                    option: answer.option.clone(),
+                    voters: answer_voters.map(|it| it.voters),
+                    chosen: answer_voters.map_or(false, |it| it.chosen),
+                    correct: answer_voters.map_or(false, |it| it.correct),
+                }
+            })
+            .collect();
+        PollInfo {
+            id: poll.raw.id,
+            question: poll.raw.question.to_string(),
+            answers,
+            closed: poll.raw.closed,
+            public_voters: poll.raw.public_voters,
+            multiple_choice: poll.raw.multiple_choice,
+            quiz: poll.raw.quiz,
+            close_date: poll.raw.close_date.map(to_date),
+            total_voters: results.and_then(|it| it.total_voters),
+            solution: results.and_then(|it| it.solution.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WebPageInfo {
+    pub id: i64,
+    pub url: Option<String>,
+    pub display_url: Option<String>,
+    pub site_name: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub embed_type: Option<String>,
+    pub embed_url: Option<String>,
+    /// Preview photo of the page. Saved only if the corresponding option is enabled
+    pub photo: Option<Box<Attachment>>,
+}
+
+impl From<&tl::enums::WebPage> for WebPageInfo {
+    fn from(page: &tl::enums::WebPage) -> Self {
+        match page {
+            tl::enums::WebPage::Page(page) => WebPageInfo {
+                id: page.id,
+                url: Some(page.url.to_string()),
+                display_url: Some(page.display_url.to_string()),
+                site_name: page.site_name.clone(),
+                title: page.title.clone(),
+                description: page.description.clone(),
+                embed_type: page.r#type.clone(),
+                embed_url: page.embed_url.clone(),
+                photo: None,
+            },
+            tl::enums::WebPage::Empty(page) => WebPageInfo::pending(page.id),
+            tl::enums::WebPage::Pending(page) => WebPageInfo::pending(page.id),
+            tl::enums::WebPage::NotModified(_) => WebPageInfo::pending(0),
+        }
+    }
+}
+
+impl WebPageInfo {
+    fn pending(id: i64) -> WebPageInfo {
+        WebPageInfo {
+            id,
+            url: None,
+            display_url: None,
+            site_name: None,
+            title: None,
+            description: None,
+            embed_type: None,
+            embed_url: None,
+            photo: None,
+        }
+    }
+}
+
+/// Date of the Telegram API
+pub(crate) fn to_date(timestamp: i32) -> DateTime<Utc> {
+    DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(timestamp as i64, 0), Utc)
+}
+
+/// Name of the user as it's shown in Telegram. Users may have only the first or only the last name
+pub(crate) fn user_name(first_name: Option<&str>, last_name: Option<&str>) -> Option<String> {
+    match (first_name, last_name) {
+        (Some(first_name), Some(last_name)) => Some(format!("{} {}", first_name, last_name)),
+        (Some(first_name), None) => Some(first_name.to_string()),
+        (None, Some(last_name)) => Some(last_name.to_string()),
+        (None, None) => None,
+    }
+}
+
+/// Names of the users and chats that are returned together with the response
+pub(crate) fn peer_names(
+    users: &[tl::enums::User],
+    chats: &[tl::enums::Chat],
+) -> HashMap<PeerInfo, String> {
+    let mut res = HashMap::new();
+    for user in users {
+        if let tl::enums::User::User(user) = user {
+            if let Some(name) = user_name(user.first_name.as_deref(), user.last_name.as_deref()) {
+                res.insert(PeerInfo::User { id: user.id }, name);
+            }
+        }
+    }
+    for chat in chats {
+        match chat {
+            tl::enums::Chat::Chat(chat) => {
+                res.insert(PeerInfo::Chat { id: chat.id }, chat.title.clone());
+            }
+            tl::enums::Chat::Channel(channel) => {
+                res.insert(PeerInfo::Channel { id: channel.id }, channel.title.clone());
+            }
+            _ => {}
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use crate::types::{user_name, Attachment, MessageInfo};
+
+    #[test]
+    fn old_message_format() {
+        let data = r#"{
+            "text": "Hello",
+            "id": 10,
+            "date": "2021-07-24T10:00:00Z",
+            "attachment": { "File": { "id": 1, "path": "../media/files/1@file.txt" } },
+            "edit_date": null,
+            "mentioned": false,
+            "outgoing": true,
+            "pinned": false,
+            "sender_id": 42,
+            "sender_name": "Alice",
+            "forwarded_from": { "from_id": 43, "from_name": "Bob", "date": "2021-07-23T10:00:00Z" },
+            "reply_to": { "to_message_id": 9 },
+            "action": null
+        }"#;
+        let message: MessageInfo = serde_json::from_str(data).unwrap();
+        assert_eq!(10, message.id);
+        assert!(matches!(message.attachment, Some(Attachment::File(_))));
+        let reply = message.reply_to.unwrap();
+        assert_eq!(Some(9), reply.to_message_id);
+        assert!(!reply.forum_topic);
+        assert!(!reply.to_scheduled);
+    }
+
+    #[test]
+    fn user_names() {
+        assert_eq!(
+            Some("Alice Smith".to_string()),
+            user_name(Some("Alice"), Some("Smith"))
+        );
+        assert_eq!(Some("Alice".to_string()), user_name(Some("Alice"), None));
+        assert_eq!(Some("Smith".to_string()), user_name(None, Some("Smith")));
+        assert_eq!(None, user_name(None, None));
+    }
+}
@@ -0,0 +1,233 @@
+/*
+ * tg_backup - backup your messages from the Telegram messenger
+ * Copyright 2021-2021 Alex Plate
+ *
+ * This file is part of tg_backup.
+ *
+ * tg_backup is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * tg_backup is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with tg_backup.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::types::to_date;
+use chrono::{DateTime, Utc};
+use grammers_client::types::User;
+use grammers_client::Client;
+use grammers_mtproto::mtp::RpcError;
+use grammers_mtsender::InvocationError;
+use grammers_tl_types as tl;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+
+const COMMON_CHATS_LIMIT: i32 = 100;
+
+/// Additional information about the user that requires separate requests
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct UserDetails {
+    pub about: Option<String>,
+    /// Phone is presented only if it's visible to us
+    pub phone: Option<String>,
+    pub bot: bool,
+    pub bot_info: Option<BotInfo>,
+    pub premium: bool,
+    pub common_chats: Vec<CommonChat>,
+}
+
+/// Last seen status of the user. It changes on almost every run, so it's saved
+/// separately from the members.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(tag = "type")]
+pub enum UserStatusInfo {
+    Online { expires: DateTime<Utc> },
+    Offline { was_online: DateTime<Utc> },
+    Recently,
+    LastWeek,
+    LastMonth,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct BotInfo {
+    pub description: Option<String>,
+    pub commands: Vec<BotCommand>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct BotCommand {
+    pub command: String,
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct CommonChat {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Loads details of users. Every user is loaded only once per run.
+pub struct UsersDetails {
+    cache: Mutex<HashMap<i64, UserDetails>>,
+}
+
+impl UsersDetails {
+    pub fn create() -> UsersDetails {
+        UsersDetails {
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, client: &Client, user: &User) -> UserDetails {
+        if let Some(details) = self.cache.lock().unwrap().get(&user.id()) {
+            return details.clone();
+        }
+        let details = load_details(client, user).await;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(user.id(), details.clone());
+        details
+    }
+}
+
+async fn load_details(client: &Client, user: &User) -> UserDetails {
+    let raw = &user.raw;
+    let mut details = UserDetails {
+        about: None,
+        phone: raw.phone.clone(),
+        bot: raw.bot,
+        bot_info: None,
+        premium: raw.premium,
+        common_chats: vec![],
+    };
+    // Deleted users have no full info
+    if raw.deleted {
+        return details;
+    }
+    let input_user = if raw.is_self {
+        tl::enums::InputUser::UserSelf
+    } else {
+        tl::enums::InputUser::User(tl::types::InputUser {
+            user_id: raw.id,
+            access_hash: raw.access_hash.unwrap_or(0),
+        })
+    };
+
+    let request = tl::functions::users::GetFullUser {
+        id: input_user.clone(),
+    };
+    match invoke(client, &request).await {
+        Ok(tl::enums::users::UserFull::Full(full)) => {
+            let tl::enums::UserFull::Full(full_user) = full.full_user;
+            details.about = full_user.about;
+            details.bot_info = full_user
+                .bot_info
+                .map(|tl::enums::BotInfo::Info(info)| BotInfo {
+                    description: info.description,
+                    commands: info
+                        .commands
+                        .unwrap_or(vec![])
+                        .into_iter()
+                        .map(|tl::enums::BotCommand::Command(command)| BotCommand {
+                            command: command.command,
+                            description: command.description,
+                        })
+                        .collect(),
+                });
+        }
+        Err(e) => log::error!("Cannot load full info of {}: {}", raw.id, e),
+    }
+
+    // There are no common chats with myself
+    if raw.is_self {
+        return details;
+    }
+    let request = tl::functions::messages::GetCommonChats {
+        user_id: input_user,
+        max_id: 0,
+        limit: COMMON_CHATS_LIMIT,
+    };
+    match invoke(client, &request).await {
+        Ok(tl::enums::messages::Chats::Chats(data)) => {
+            details.common_chats = to_common_chats(data.chats)
+        }
+        Ok(tl::enums::messages::Chats::Slice(data)) => {
+            details.common_chats = to_common_chats(data.chats)
+        }
+        Err(e) => log::error!("Cannot load common chats of {}: {}", raw.id, e),
+    }
+    details
+}
+
+/// Invoke the request and repeat it after the flood wait
+async fn invoke<R: tl::RemoteCall>(
+    client: &Client,
+    request: &R,
+) -> Result<R::Return, InvocationError> {
+    loop {
+        match client.invoke(request).await {
+            Err(InvocationError::Rpc(RpcError {
+                name,
+                value: Some(wait_time),
+                ..
+            })) if name == "FLOOD_WAIT" => {
+                log::warn!("Flood wait: {}", wait_time);
+                sleep(Duration::from_secs(wait_time as u64));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Last seen status of the users
+pub fn statuses(users: &[User]) -> HashMap<i64, UserStatusInfo> {
+    users
+        .iter()
+        .filter_map(|user| {
+            let status = user.raw.status.as_ref().and_then(to_status)?;
+            Some((user.id(), status))
+        })
+        .collect()
+}
+
+fn to_status(status: &tl::enums::UserStatus) -> Option<UserStatusInfo> {
+    match status {
+        tl::enums::UserStatus::Empty => None,
+        tl::enums::UserStatus::Online(status) => Some(UserStatusInfo::Online {
+            expires: to_date(status.expires),
+        }),
+        tl::enums::UserStatus::Offline(status) => Some(UserStatusInfo::Offline {
+            was_online: to_date(status.was_online),
+        }),
+        tl::enums::UserStatus::Recently { .. } => Some(UserStatusInfo::Recently),
+        tl::enums::UserStatus::LastWeek { .. } => Some(UserStatusInfo::LastWeek),
+        tl::enums::UserStatus::LastMonth { .. } => Some(UserStatusInfo::LastMonth),
+    }
+}
+
+fn to_common_chats(chats: Vec<tl::enums::Chat>) -> Vec<CommonChat> {
+    chats
+        .into_iter()
+        .filter_map(|chat| match chat {
+            tl::enums::Chat::Chat(chat) => Some(CommonChat {
+                id: chat.id,
+                name: chat.title,
+            }),
+            tl::enums::Chat::Channel(channel) => Some(CommonChat {
+                id: channel.id,
+                name: channel.title,
+            }),
+            _ => None,
+        })
+        .collect()
+}